@@ -0,0 +1,161 @@
+use cgmath::{dot, vec2, InnerSpace, Vector2};
+use elma::lev::Polygon;
+use std::mem;
+
+/// Start, end, unit direction and length of a segment.
+type Segment = (Vector2<f64>, Vector2<f64>, Vector2<f64>, f64);
+
+/// The uniform grid `Segments` used before it switched to flat cell storage, kept for
+/// comparison.
+pub struct Legacy {
+    min: Vector2<f64>,
+    segments: Vec<Segment>,
+    width: usize,
+    height: usize,
+    /// Segment indices of each cell, in the order they were scanned.
+    pub table: Vec<Vec<u32>>,
+}
+
+impl Legacy {
+    pub fn new(polygons: &[Polygon]) -> Legacy {
+        let vertices = || {
+            polygons
+                .iter()
+                .filter(|p| !p.grass)
+                .flat_map(|p| &p.vertices)
+        };
+        let min_x = vertices().map(|v| v.x).fold(f64::INFINITY, f64::min);
+        let min_y = vertices().map(|v| v.y).fold(f64::INFINITY, f64::min);
+        let max_x = vertices().map(|v| v.x).fold(-f64::INFINITY, f64::max);
+        let max_y = vertices().map(|v| v.y).fold(-f64::INFINITY, f64::max);
+
+        let width = (max_x - min_x).ceil() as usize + 1;
+        let height = (max_y - min_y).ceil() as usize + 1;
+
+        let mut segments = Vec::new();
+        let mut table = vec![vec![]; width * height];
+
+        for polygon in polygons {
+            if polygon.grass {
+                continue;
+            }
+            let mut prev = polygon.vertices.last().unwrap().clone();
+            for vertex in &polygon.vertices {
+                let a = vec2(prev.x, prev.y);
+                let b = vec2(vertex.x, vertex.y);
+                let ab = b - a;
+                let length = ab.magnitude();
+                prev = vertex.clone();
+                if length == 0.0 {
+                    continue;
+                }
+
+                let index = segments.len();
+                segments.push((a, b, ab / length, length));
+
+                let mut y0 = (a.y - min_y).floor();
+                let mut y1 = (b.y - min_y).floor();
+                if y0 > y1 {
+                    mem::swap(&mut y0, &mut y1);
+                }
+
+                let mut lower = a;
+                let mut upper = b;
+                if lower.y > upper.y {
+                    mem::swap(&mut lower, &mut upper);
+                }
+
+                for y in (y0 as usize)..=(y1 as usize) {
+                    let y0 = min_y + y as f64;
+                    let y1 = min_y + y as f64 + 1.0;
+
+                    let lu = upper - lower;
+                    let one = if lower.y >= y0 - 0.1 {
+                        lower.x
+                    } else {
+                        lower.x + lu.x * (y0 - lower.y) / lu.y
+                    };
+                    let two = if upper.y <= y1 + 0.1 {
+                        upper.x
+                    } else {
+                        lower.x + lu.x * (y1 - lower.y) / lu.y
+                    };
+
+                    let mut x0 = (one - min_x).floor();
+                    let mut x1 = (two - min_x).floor();
+                    if x0 > x1 {
+                        mem::swap(&mut x0, &mut x1);
+                    }
+
+                    for x in (x0 as usize)..=(x1 as usize) {
+                        table[y * width + x].push(index as u32);
+                    }
+                }
+            }
+        }
+
+        Legacy {
+            min: vec2(min_x, min_y),
+            segments,
+            width,
+            height,
+            table,
+        }
+    }
+
+    fn cell(&self, pos: Vector2<f64>) -> &[u32] {
+        if pos.x < self.min.x - 1.0 || pos.y < self.min.y - 1.0 {
+            return &[];
+        }
+
+        let pos = pos - self.min;
+        let x = pos.x.floor() as usize;
+        let y = pos.y.floor() as usize;
+        if x >= self.width || y >= self.height {
+            return &[];
+        }
+
+        &self.table[y * self.width + x]
+    }
+
+    pub fn touches(&self, pos: Vector2<f64>, r: f64) -> bool {
+        let corners = [
+            pos + vec2(r, r),
+            pos + vec2(r, -r),
+            pos + vec2(-r, -r),
+            pos + vec2(-r, r),
+        ];
+
+        let mut collisions = [vec2(0.0, 0.0); 2];
+        let mut collision = false;
+        for &corner in &corners {
+            for &i in self.cell(corner) {
+                let (a, b, dir, length) = self.segments[i as usize];
+                let vector = pos - a;
+                let fraction = dot(vector, dir);
+                let point = if fraction < 0.0 {
+                    Some(a).filter(|_| vector.magnitude() < r)
+                } else if fraction <= length {
+                    Some(a + dir * fraction).filter(|_| dir.perp_dot(vector).abs() <= r)
+                } else {
+                    Some(b).filter(|_| (pos - b).magnitude() < r)
+                };
+
+                if let Some(point) = point {
+                    if !collision {
+                        collisions[0] = point;
+                        collision = true;
+                    } else {
+                        collisions[1] = point;
+                        if (collisions[0] - collisions[1]).magnitude() >= 0.1 {
+                            return true;
+                        }
+                        collisions[0] = (collisions[0] + collisions[1]) * 0.5;
+                    }
+                }
+            }
+        }
+
+        collision
+    }
+}
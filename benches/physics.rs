@@ -0,0 +1,130 @@
+use cgmath::vec2;
+use criterion::measurement::WallTime;
+use criterion::{criterion_group, criterion_main, BenchmarkGroup, Criterion};
+use elma::lev::{Level, Object, ObjectType, Polygon};
+use elma::rec::{Event, EventType, Frame, Ride};
+use elma::Position;
+use elma2::game::{GameState, FRAME_TIME};
+use elma2::physics::{Control, Moto, MotoBatch, Segments};
+use elma2::replay::resimulate;
+use std::time::Duration;
+
+const THROTTLE: Control = Control {
+    throttle: true,
+    brake: false,
+    turn: false,
+    volt_left: false,
+    volt_right: false,
+    alt_volt: false,
+};
+
+/// Long box with a floor made of segments `step` long.
+fn ground(step: f64) -> Vec<Polygon> {
+    let mut vertices = vec![Position::new(0.0, 20.0)];
+    let mut x = 0.0f64;
+    while x <= 500.0 {
+        let y = if step < 1.0 {
+            (x * 0.5).sin() * 0.1
+        } else {
+            0.0
+        };
+        vertices.push(Position::new(x, y));
+        x += step;
+    }
+    vertices.push(Position::new(500.0, 20.0));
+
+    vec![Polygon {
+        grass: false,
+        vertices,
+    }]
+}
+
+fn level(polygons: Vec<Polygon>) -> Level {
+    let mut level = Level::new();
+    level.polygons = polygons;
+    level.objects = vec![Object {
+        position: Position::new(5.0, 1.0),
+        object_type: ObjectType::Player,
+    }];
+    level
+}
+
+/// One second of physics time riding right on the ground.
+fn ride(group: &mut BenchmarkGroup<WallTime>, name: &str, polygons: &[Polygon]) {
+    let segments = Segments::new(polygons).unwrap();
+    group.bench_function(name, |b| {
+        b.iter(|| {
+            let mut moto = Moto::new(vec2(5.0, 1.0));
+            moto.advance(THROTTLE, 1.0, &segments, &mut ());
+            moto.bike.position
+        })
+    });
+}
+
+/// Ride with a turn every five seconds, recorded like a replay.
+fn record(level: Level, frames: usize) -> Ride {
+    let mut game_state = GameState::from_level(level).unwrap();
+    let mut ride = Ride::new();
+    for i in 0..frames {
+        let t = i as f64 * FRAME_TIME;
+        // Throttle only while the bike is level enough not to flip over.
+        let throttle = game_state.moto.bike.angular_position.abs() < 0.2;
+        let control = Control {
+            throttle,
+            turn: i % 150 == 1,
+            ..Control::default()
+        };
+        game_state.advance(control, t, &mut ());
+        if control.turn {
+            ride.events.push(Event {
+                time: game_state.moto.last_turn(),
+                event_type: EventType::Turn,
+            });
+        }
+
+        let position = game_state.moto.bike.position;
+        ride.frames.push(Frame {
+            bike: Position::new(position.x as f32, position.y as f32),
+            throttle_and_dir: throttle as u8,
+            ..Frame::default()
+        });
+    }
+    ride
+}
+
+fn physics(c: &mut Criterion) {
+    let mut group = c.benchmark_group("physics");
+    group.sample_size(10);
+    ride(&mut group, "flat", &ground(10.0));
+    ride(&mut group, "dense", &ground(0.05));
+
+    // A tenth of a second for many bikes at once.
+    let segments = Segments::new(&ground(0.05)).unwrap();
+    let positions: Vec<_> = (0..64).map(|i| vec2(5.0 + i as f64 * 2.0, 1.0)).collect();
+    group.bench_function("batch_64", |b| {
+        b.iter(|| {
+            let mut batch = MotoBatch::new(&positions);
+            batch.controls = vec![THROTTLE; batch.len()];
+            batch.advance(0.1, &segments);
+            batch.positions()[0]
+        })
+    });
+    group.finish();
+
+    // A minute of riding.
+    let recorded = record(level(ground(0.05)), 1800);
+    let mut group = c.benchmark_group("replay");
+    group.sample_size(10);
+    group.measurement_time(Duration::from_secs(20));
+    group.bench_function("resimulate", |b| {
+        b.iter(|| {
+            resimulate(level(ground(0.05)), &recorded)
+                .unwrap()
+                .max_divergence
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, physics);
+criterion_main!(benches);
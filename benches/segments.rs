@@ -0,0 +1,106 @@
+use cgmath::{vec2, Vector2};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use elma::lev::Polygon;
+use elma::Position;
+use elma2::physics::{Segments, HEAD_RADIUS, WHEEL_RADIUS};
+use legacy::Legacy;
+use std::f64::consts::PI;
+
+mod legacy;
+
+/// Ground with a bumpy floor, `width` units wide.
+fn bumpy(width: f64, step: f64) -> Vec<Polygon> {
+    let mut vertices = vec![Position::new(0.0, 20.0)];
+    let mut x = 0.0;
+    while x <= width {
+        vertices.push(Position::new(x, (x * 0.7).sin() * 2.0));
+        x += step;
+    }
+    vertices.push(Position::new(width, 20.0));
+
+    vec![Polygon {
+        grass: false,
+        vertices,
+    }]
+}
+
+/// Two small rooms far apart, mostly empty cells.
+fn sparse() -> Vec<Polygon> {
+    let room = |x: f64| Polygon {
+        grass: false,
+        vertices: vec![
+            Position::new(x, 0.0),
+            Position::new(x + 10.0, 0.0),
+            Position::new(x + 10.0, 5.0),
+            Position::new(x, 5.0),
+        ],
+    };
+    vec![room(0.0), room(2000.0)]
+}
+
+/// Many small round stones in a box.
+fn dense() -> Vec<Polygon> {
+    let mut polygons = bumpy(100.0, 1.0);
+    for i in 0..400 {
+        let center = vec2((i % 40) as f64 * 2.5 + 1.0, (i / 40) as f64 * 1.5 + 4.0);
+        polygons.push(Polygon {
+            grass: false,
+            vertices: (0..16)
+                .map(|j| {
+                    let angle = j as f64 * 2.0 * PI / 16.0;
+                    Position::new(center.x + angle.cos() * 0.5, center.y + angle.sin() * 0.5)
+                })
+                .collect(),
+        });
+    }
+    polygons
+}
+
+fn queries(polygons: &[Polygon]) -> Vec<Vector2<f64>> {
+    let vertices = polygons.iter().flat_map(|p| &p.vertices);
+    vertices
+        .flat_map(|v| (0..4).map(move |i| vec2(v.x + 0.13 * i as f64, v.y + 0.21 * i as f64)))
+        .collect()
+}
+
+fn bench_level(c: &mut Criterion, name: &str, polygons: &[Polygon]) {
+    let segments = Segments::new(polygons).unwrap();
+    let legacy = Legacy::new(polygons);
+    let queries = queries(polygons);
+
+    c.bench_function(&format!("{}/build", name), |b| {
+        b.iter(|| Segments::new(black_box(polygons)).unwrap())
+    });
+    c.bench_function(&format!("{}/build_legacy", name), |b| {
+        b.iter(|| Legacy::new(black_box(polygons)))
+    });
+    c.bench_function(&format!("{}/touches", name), |b| {
+        b.iter(|| {
+            queries
+                .iter()
+                .filter(|&&pos| {
+                    segments.touches(pos, WHEEL_RADIUS) || segments.touches(pos, HEAD_RADIUS)
+                })
+                .count()
+        })
+    });
+    c.bench_function(&format!("{}/touches_legacy", name), |b| {
+        b.iter(|| {
+            queries
+                .iter()
+                .filter(|&&pos| {
+                    legacy.touches(pos, WHEEL_RADIUS) || legacy.touches(pos, HEAD_RADIUS)
+                })
+                .count()
+        })
+    });
+}
+
+fn segments(c: &mut Criterion) {
+    bench_level(c, "bumpy", &bumpy(1000.0, 0.3));
+    bench_level(c, "sparse", &sparse());
+    bench_level(c, "dense", &dense());
+}
+
+criterion_group!(benches, segments);
+criterion_main!(benches);
@@ -1,15 +1,21 @@
+use crate::font::{GLYPHS, GLYPH_HEIGHT, GLYPH_WIDTH};
 use cgmath::{vec2, Vector2};
 use elma::lgr::{Picture, PictureType, Transparency, LGR};
 use rect_packer::{Config, Packer, Rect};
 use std::collections::BTreeMap;
 
+/// Size of a built-in font pixel in atlas pixels.
+const FONT_SCALE: usize = 4;
+
 pub struct Atlas {
     pub sprites: BTreeMap<String, Sprite>,
+    pub glyphs: BTreeMap<char, Sprite>,
     pub data: Vec<u8>,
     pub width: i32,
     pub height: i32,
 }
 
+#[derive(Copy, Clone)]
 pub struct Sprite {
     pub bounds: [f32; 4],
     pub size: Vector2<f64>,
@@ -103,30 +109,45 @@ impl Atlas {
                 }
             }
 
-            let mut left = rect.x as f32;
-            let mut top = rect.y as f32;
-            let mut right = left + rect.width as f32;
-            let mut bottom = top + rect.height as f32;
-            if kind == PictureType::Texture {
-                left += 0.5;
-                top += 0.5;
-                right -= 0.5;
-                bottom -= 0.5;
-            }
             sprites.insert(
                 name.to_owned(),
-                Sprite {
-                    bounds: [
-                        left / atlas_width as f32,
-                        top / atlas_height as f32,
-                        right / atlas_width as f32,
-                        bottom / atlas_height as f32,
-                    ],
-                    size: vec2(width as f64, height as f64),
-                },
+                sprite(
+                    rect,
+                    atlas_width,
+                    atlas_height,
+                    kind == PictureType::Texture,
+                ),
             );
         }
 
+        let mut glyphs = BTreeMap::new();
+        for &(character, rows) in GLYPHS {
+            let width = GLYPH_WIDTH * FONT_SCALE;
+            let height = GLYPH_HEIGHT * FONT_SCALE;
+            let rect = rect_packer
+                .pack(width as i32, height as i32, false)
+                .unwrap();
+
+            for row in 0..height {
+                for x in 0..width {
+                    let bit = rows[row / FONT_SCALE] >> (GLYPH_WIDTH - 1 - x / FONT_SCALE) & 1;
+                    let i = index(row, x, atlas_width, rect);
+                    for c in 0..4 {
+                        data[i + c] = 255 * bit;
+                    }
+                }
+            }
+
+            glyphs.insert(character, sprite(rect, atlas_width, atlas_height, false));
+        }
+
+        // Prefer timer digits from the LGR.
+        for c in '0'..='9' {
+            if let Some(&sprite) = sprites.get(&format!("TIMER{}", c)) {
+                glyphs.insert(c, sprite);
+            }
+        }
+
         /*    RgbaImage::from_raw(atlas_width as _, atlas_height as _, texture.clone())
         .unwrap()
         .save("texture.png")
@@ -134,6 +155,7 @@ impl Atlas {
 
         Atlas {
             sprites,
+            glyphs,
             data,
             width: atlas_width,
             height: atlas_height,
@@ -146,6 +168,29 @@ impl Atlas {
     }
 }
 
+fn sprite(rect: Rect, atlas_width: i32, atlas_height: i32, texture: bool) -> Sprite {
+    let mut left = rect.x as f32;
+    let mut top = rect.y as f32;
+    let mut right = left + rect.width as f32;
+    let mut bottom = top + rect.height as f32;
+    if texture {
+        left += 0.5;
+        top += 0.5;
+        right -= 0.5;
+        bottom -= 0.5;
+    }
+
+    Sprite {
+        bounds: [
+            left / atlas_width as f32,
+            top / atlas_height as f32,
+            right / atlas_width as f32,
+            bottom / atlas_height as f32,
+        ],
+        size: vec2(rect.width as f64, rect.height as f64),
+    }
+}
+
 fn index(row: usize, column: usize, atlas_width: i32, rect: Rect) -> usize {
     ((rect.y as usize + row) * atlas_width as usize + rect.x as usize + column) * 4
 }
@@ -0,0 +1,898 @@
+//! Game sounds mixed from physics events into a mono sample stream.
+
+use crate::check::LevelError;
+use crate::game::{Outcome, FRAME_TIME, TIME_SCALE};
+use crate::physics::{Events, Moto, MAX_WHEEL_ANGULAR_VELOCITY};
+use crate::replay::simulate;
+use elma::lev::Level;
+use elma::rec::{EventType, Replay, Ride};
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+/// Directory with sound files replacing the synthesized sounds.
+pub const SOUNDS_PATH: &str = "sounds";
+
+/// Sample rate of the WAV and null outputs.
+pub const SAMPLE_RATE: u32 = 44100;
+
+/// Most sounds playing at once, the oldest one is cut off beyond this.
+const MAX_VOICES: usize = 16;
+
+/// Engine loop speed relative to its recorded pitch at standstill and at full wheel speed.
+const ENGINE_PITCH: (f32, f32) = (1.0, 3.0);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Sound {
+    Bump,
+    Apple,
+    Volt,
+    Turn,
+    Death,
+    Finish,
+}
+
+/// Mono samples of every sound at the output's sample rate.
+pub struct Sounds {
+    bump: Vec<f32>,
+    apple: Vec<f32>,
+    volt: Vec<f32>,
+    turn: Vec<f32>,
+    death: Vec<f32>,
+    finish: Vec<f32>,
+    /// Looped, played faster as the wheel spins faster.
+    engine: Vec<f32>,
+    /// Looped, louder the harder the suspension is pushed in.
+    friction: Vec<f32>,
+}
+
+impl Sounds {
+    /// Load `bump.wav`, `apple.wav`, `volt.wav`, `turn.wav`, `death.wav`, `finish.wav`,
+    /// `engine.wav` and `friction.wav` from `dir`, for example ones extracted from the original
+    /// game. Sounds missing or failing to load are synthesized.
+    pub fn load(dir: &Path, sample_rate: u32) -> Sounds {
+        let synthesized = Sounds::synthesized(sample_rate);
+        let load = |name: &str, fallback: Vec<f32>| {
+            let path = dir.join(name);
+            if !path.exists() {
+                return fallback;
+            }
+            read_wav(&path, sample_rate).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                fallback
+            })
+        };
+
+        Sounds {
+            bump: load("bump.wav", synthesized.bump),
+            apple: load("apple.wav", synthesized.apple),
+            volt: load("volt.wav", synthesized.volt),
+            turn: load("turn.wav", synthesized.turn),
+            death: load("death.wav", synthesized.death),
+            finish: load("finish.wav", synthesized.finish),
+            engine: load("engine.wav", synthesized.engine),
+            friction: load("friction.wav", synthesized.friction),
+        }
+    }
+
+    pub fn synthesized(sample_rate: u32) -> Sounds {
+        let rate = sample_rate as f32;
+        let tone = |frequency: f32, length: f32, decay: f32| -> Vec<f32> {
+            (0..(length * rate) as usize)
+                .map(|i| {
+                    let t = i as f32 / rate;
+                    (2.0 * PI * frequency * t).sin() * (-t / decay).exp()
+                })
+                .collect()
+        };
+        let notes = |frequencies: &[f32], length: f32| -> Vec<f32> {
+            frequencies
+                .iter()
+                .flat_map(|&frequency| tone(frequency, length, length * 0.5))
+                .collect()
+        };
+
+        // Sweep down with the phase integrated, so the pitch changes smoothly.
+        let mut phase = 0.0f32;
+        let death = (0..(0.6 * rate) as usize)
+            .map(|i| {
+                let t = i as f32 / 0.6 / rate;
+                phase += 2.0 * PI * (400.0 - 320.0 * t) / rate;
+                phase.sin().signum() * 0.5 * (1.0 - t)
+            })
+            .collect();
+
+        // One period of a 50 Hz sawtooth.
+        let period = (rate / 50.0) as usize;
+        let engine = (0..period)
+            .map(|i| (i as f32 / period as f32 * 2.0 - 1.0) * 0.5)
+            .collect();
+
+        // Low passed noise from a fixed seed, so it sounds the same every time.
+        let noise = |length: f32, smoothing: f32| -> Vec<f32> {
+            let mut seed = 1u32;
+            let mut value = 0.0;
+            (0..(length * rate) as usize)
+                .map(|_| {
+                    seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                    let noise = (seed >> 16) as f32 / 32768.0 - 1.0;
+                    value += (noise - value) * smoothing;
+                    value
+                })
+                .collect()
+        };
+
+        // A swoosh swelling up and fading out.
+        let swoosh = |length: f32, smoothing: f32, volume: f32| -> Vec<f32> {
+            let mut samples = noise(length, smoothing);
+            let length = samples.len() as f32;
+            for (i, sample) in samples.iter_mut().enumerate() {
+                *sample *= (i as f32 / length * PI).sin() * volume;
+            }
+            samples
+        };
+
+        Sounds {
+            bump: tone(70.0, 0.15, 0.04),
+            apple: notes(&[880.0, 1320.0], 0.08),
+            volt: swoosh(0.15, 0.3, 0.6),
+            turn: swoosh(0.08, 0.6, 0.4),
+            death,
+            finish: notes(&[523.0, 659.0, 784.0, 1047.0], 0.12),
+            engine,
+            friction: noise(0.5, 0.1),
+        }
+    }
+
+    fn get(&self, sound: Sound) -> &[f32] {
+        match sound {
+            Sound::Bump => &self.bump,
+            Sound::Apple => &self.apple,
+            Sound::Volt => &self.volt,
+            Sound::Turn => &self.turn,
+            Sound::Death => &self.death,
+            Sound::Finish => &self.finish,
+        }
+    }
+}
+
+/// Mono samples of a WAV file resampled to `sample_rate`, channels are averaged.
+fn read_wav(path: &Path, sample_rate: u32) -> Result<Vec<f32>, String> {
+    let error = |e: hound::Error| format!("{}: {}", path.display(), e);
+    let mut reader = hound::WavReader::open(path).map_err(error)?;
+    let spec = reader.spec();
+
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>(),
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1u32 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 * scale))
+                .collect::<Result<_, _>>()
+        }
+    }
+    .map_err(error)?;
+
+    let channels = spec.channels as usize;
+    let mono: Vec<f32> = samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+    if mono.is_empty() || spec.sample_rate == sample_rate {
+        return Ok(mono);
+    }
+
+    // Linear interpolation is plenty for game sounds.
+    let step = spec.sample_rate as f64 / sample_rate as f64;
+    let length = ((mono.len() - 1) as f64 / step) as usize + 1;
+    Ok((0..length)
+        .map(|i| {
+            let position = i as f64 * step;
+            let index = position as usize;
+            let next = mono[(index + 1).min(mono.len() - 1)];
+            let fraction = (position - index as f64) as f32;
+            mono[index] + (next - mono[index]) * fraction
+        })
+        .collect())
+}
+
+/// Where mixed samples go.
+pub trait Output {
+    fn sample_rate(&self) -> u32;
+
+    fn write(&mut self, samples: &[f32]);
+
+    /// Flush everything written, the output isn't written to afterwards. Outputs that can fail
+    /// return their first error here.
+    fn finish(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Drops all samples, for running without sound.
+pub struct NullOutput;
+
+impl Output for NullOutput {
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn write(&mut self, _samples: &[f32]) {}
+}
+
+/// Writes 16-bit mono WAV.
+pub struct WavOutput {
+    path: PathBuf,
+    /// Dropped after the first error or on finish.
+    writer: Option<hound::WavWriter<BufWriter<File>>>,
+    /// First write error, returned by `finish`.
+    error: Option<String>,
+}
+
+impl WavOutput {
+    pub fn create(path: &Path) -> Result<WavOutput, String> {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let writer = hound::WavWriter::create(path, spec)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(WavOutput {
+            path: path.to_path_buf(),
+            writer: Some(writer),
+            error: None,
+        })
+    }
+}
+
+impl Output for WavOutput {
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn write(&mut self, samples: &[f32]) {
+        let writer = match self.writer.as_mut() {
+            Some(writer) => writer,
+            None => return,
+        };
+        for &sample in samples {
+            let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            if let Err(e) = writer.write_sample(sample) {
+                self.error = Some(format!("{}: {}", self.path.display(), e));
+                self.writer = None;
+                return;
+            }
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), String> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        match self.writer.take() {
+            Some(writer) => writer
+                .finalize()
+                .map_err(|e| format!("{}: {}", self.path.display(), e)),
+            None => Ok(()),
+        }
+    }
+}
+
+struct Voice {
+    sound: Sound,
+    position: usize,
+    volume: f32,
+}
+
+/// Sound state of a running game. Bumps, apples, volts and turns come in as physics events, the
+/// engine and outcome sounds from `update`, and `render` mixes and writes the samples of elapsed
+/// time.
+pub struct Audio {
+    sounds: Sounds,
+    output: Box<dyn Output>,
+    voices: Vec<Voice>,
+    /// Positions in the engine and friction loops.
+    engine_position: f32,
+    friction_position: usize,
+    engine_pitch: f32,
+    engine_volume: f32,
+    /// Loudest dorzol volume since the last render.
+    dorzol: f64,
+    friction_volume: f32,
+    outcome: Outcome,
+    /// Samples not yet rendered, carried over between calls.
+    pending: f64,
+}
+
+impl Audio {
+    pub fn new(sounds: Sounds, output: Box<dyn Output>) -> Audio {
+        Audio {
+            sounds,
+            output,
+            voices: Vec::new(),
+            engine_position: 0.0,
+            friction_position: 0,
+            engine_pitch: ENGINE_PITCH.0,
+            engine_volume: 0.0,
+            dorzol: 0.0,
+            friction_volume: 0.0,
+            outcome: Outcome::Playing,
+            pending: 0.0,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.output.sample_rate()
+    }
+
+    pub fn play(&mut self, sound: Sound, volume: f32) {
+        // Sounds come in bursts, like bumps while a wheel settles or both volts of an alt volt.
+        // Keep only the loudest.
+        let burst = self.sample_rate() as usize / 20;
+        if let Some(voice) = self
+            .voices
+            .iter_mut()
+            .find(|voice| voice.sound == sound && voice.position < burst)
+        {
+            voice.volume = voice.volume.max(volume);
+            return;
+        }
+
+        if self.voices.len() == MAX_VOICES {
+            self.voices.remove(0);
+        }
+        self.voices.push(Voice {
+            sound,
+            position: 0,
+            volume,
+        });
+    }
+
+    /// Follow the bike's engine and play the death or finish sound when the outcome changes.
+    pub fn update(&mut self, moto: &Moto, throttle: bool, outcome: Outcome) {
+        // The driven wheel is the back one.
+        let wheel = &moto.wheels[if moto.direction { 0 } else { 1 }];
+        let speed = (wheel.angular_velocity().abs() / MAX_WHEEL_ANGULAR_VELOCITY).min(1.0) as f32;
+        self.engine_pitch = ENGINE_PITCH.0 + (ENGINE_PITCH.1 - ENGINE_PITCH.0) * speed;
+
+        self.engine_volume = match outcome {
+            Outcome::Playing if throttle => 0.3,
+            Outcome::Playing => 0.15,
+            _ => 0.0,
+        };
+
+        if outcome != self.outcome {
+            match outcome {
+                Outcome::Dead => self.play(Sound::Death, 1.0),
+                Outcome::Finished => self.play(Sound::Finish, 1.0),
+                Outcome::Playing => self.voices.clear(),
+            }
+            self.outcome = outcome;
+        }
+    }
+
+    /// Mix and output `seconds` of sound.
+    pub fn render(&mut self, seconds: f64) {
+        self.pending += seconds * self.sample_rate() as f64;
+        let length = self.pending as usize;
+        self.pending -= length as f64;
+
+        // Dorzol volumes can spike for a single step, so follow them smoothly.
+        self.friction_volume += (self.dorzol.min(1.0) as f32 - self.friction_volume) * 0.5;
+        self.dorzol = 0.0;
+
+        let mut samples = vec![0.0; length];
+        let engine = &self.sounds.engine;
+        let friction = &self.sounds.friction;
+        for sample in &mut samples {
+            if !engine.is_empty() {
+                *sample += engine[self.engine_position as usize] * self.engine_volume;
+                self.engine_position =
+                    (self.engine_position + self.engine_pitch) % engine.len() as f32;
+            }
+            if !friction.is_empty() {
+                *sample += friction[self.friction_position] * self.friction_volume;
+                self.friction_position = (self.friction_position + 1) % friction.len();
+            }
+        }
+
+        for voice in &mut self.voices {
+            let sound = self.sounds.get(voice.sound);
+            let end = (voice.position + length).min(sound.len());
+            for (sample, &value) in samples.iter_mut().zip(&sound[voice.position..end]) {
+                *sample += value * voice.volume;
+            }
+            voice.position = end;
+        }
+        let sounds = &self.sounds;
+        self.voices
+            .retain(|voice| voice.position < sounds.get(voice.sound).len());
+
+        self.output.write(&samples);
+    }
+
+    pub fn finish(&mut self) -> Result<(), String> {
+        self.output.finish()
+    }
+}
+
+impl Events for Audio {
+    fn event(&mut self, kind: EventType) {
+        match kind {
+            EventType::Ground(volume) => self.play(Sound::Bump, volume),
+            EventType::Apple => self.play(Sound::Apple, 1.0),
+            EventType::VoltRight | EventType::VoltLeft => self.play(Sound::Volt, 1.0),
+            EventType::Turn => self.play(Sound::Turn, 1.0),
+            _ => {}
+        }
+    }
+
+    fn dorzol(&mut self, volume: f64) {
+        self.dorzol = self.dorzol.max(volume);
+    }
+}
+
+/// Simulate a ride and mix its sounds along the ride's timeline.
+pub fn export(level: Level, ride: &Ride, audio: &mut Audio) -> Result<(), LevelError> {
+    let frame_seconds = FRAME_TIME / TIME_SCALE;
+    simulate(level, ride, audio, |i, game_state, audio| {
+        audio.update(
+            &game_state.moto,
+            ride.frames[i].throttle(),
+            game_state.outcome,
+        );
+        // Sounds of the steps leading up to a frame start at the previous frame.
+        if i > 0 {
+            audio.render(frame_seconds);
+        }
+    })?;
+    audio.render(frame_seconds);
+    Ok(())
+}
+
+/// Write the sound of a replay's first ride to a WAV file.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let paths: Vec<&String> = args.iter().filter(|arg| !arg.starts_with("--")).collect();
+    let (level_path, replay_path, output_path) = match paths[..] {
+        [level, replay, output] => (level, replay, output),
+        _ => return Err("usage: audio <level> <replay> <output.wav>".to_string()),
+    };
+
+    let level = Level::load(level_path).map_err(|e| format!("{}: {:?}", level_path, e))?;
+    let replay = Replay::load(replay_path).map_err(|e| format!("{}: {:?}", replay_path, e))?;
+    let ride = replay
+        .rides
+        .first()
+        .ok_or_else(|| format!("{}: no rides", replay_path))?;
+
+    let output = WavOutput::create(Path::new(output_path))?;
+    let sounds = Sounds::load(Path::new(SOUNDS_PATH), output.sample_rate());
+    let mut audio = Audio::new(sounds, Box::new(output));
+    export(level, ride, &mut audio).map_err(|e| format!("{}: {}", level_path, e))?;
+    audio.finish()
+}
+
+#[cfg(feature = "sound")]
+pub use device::DeviceOutput;
+
+#[cfg(feature = "sound")]
+mod device {
+    use super::Output;
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+    use cpal::{Sample, SampleFormat, Stream};
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+
+    /// Plays on the default sound device. Samples are queued for the device's callback, which
+    /// plays silence when the queue runs dry.
+    pub struct DeviceOutput {
+        queue: Arc<Mutex<VecDeque<f32>>>,
+        sample_rate: u32,
+        _stream: Stream,
+    }
+
+    impl DeviceOutput {
+        pub fn new() -> Result<DeviceOutput, String> {
+            let device = cpal::default_host()
+                .default_output_device()
+                .ok_or_else(|| "no sound device".to_string())?;
+            let supported = device.default_output_config().map_err(|e| e.to_string())?;
+            let config = supported.config();
+            let queue = Arc::new(Mutex::new(VecDeque::new()));
+
+            let stream = match supported.sample_format() {
+                SampleFormat::F32 => stream::<f32>(&device, &config, queue.clone()),
+                SampleFormat::I16 => stream::<i16>(&device, &config, queue.clone()),
+                SampleFormat::U16 => stream::<u16>(&device, &config, queue.clone()),
+            }?;
+            stream.play().map_err(|e| e.to_string())?;
+
+            Ok(DeviceOutput {
+                queue,
+                sample_rate: config.sample_rate.0,
+                _stream: stream,
+            })
+        }
+    }
+
+    fn stream<T: Sample>(
+        device: &cpal::Device,
+        config: &cpal::StreamConfig,
+        queue: Arc<Mutex<VecDeque<f32>>>,
+    ) -> Result<Stream, String> {
+        let channels = config.channels as usize;
+        device
+            .build_output_stream(
+                config,
+                move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                    let mut queue = queue.lock().unwrap();
+                    for frame in data.chunks_mut(channels) {
+                        let sample = queue.pop_front().unwrap_or(0.0).clamp(-1.0, 1.0);
+                        for value in frame {
+                            *value = Sample::from(&sample);
+                        }
+                    }
+                },
+                |e| eprintln!("{}", e),
+            )
+            .map_err(|e| e.to_string())
+    }
+
+    impl Output for DeviceOutput {
+        fn sample_rate(&self) -> u32 {
+            self.sample_rate
+        }
+
+        fn write(&mut self, samples: &[f32]) {
+            let mut queue = self.queue.lock().unwrap();
+            queue.extend(samples);
+            // Don't fall behind the game by more than a tenth of a second.
+            let limit = self.sample_rate as usize / 10;
+            if queue.len() > limit {
+                let excess = queue.len() - limit;
+                queue.drain(..excess);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::fs;
+    use std::rc::Rc;
+
+    /// Keeps every sample written, at a sample rate which makes lengths easy to count.
+    struct Capture(Rc<RefCell<Vec<f32>>>);
+
+    const CAPTURE_RATE: u32 = 1000;
+
+    impl Output for Capture {
+        fn sample_rate(&self) -> u32 {
+            CAPTURE_RATE
+        }
+
+        fn write(&mut self, samples: &[f32]) {
+            self.0.borrow_mut().extend_from_slice(samples);
+        }
+    }
+
+    /// Sounds which are constant, so mixed values are easy to check.
+    fn sounds(length: usize) -> Sounds {
+        Sounds {
+            bump: vec![0.5; length],
+            apple: vec![0.25; length / 2],
+            volt: vec![0.1; length],
+            turn: vec![0.4; length],
+            death: vec![0.2; length],
+            finish: vec![0.3; length],
+            engine: Vec::new(),
+            friction: Vec::new(),
+        }
+    }
+
+    fn capture(sounds: Sounds) -> (Audio, Rc<RefCell<Vec<f32>>>) {
+        let samples = Rc::new(RefCell::new(Vec::new()));
+        let audio = Audio::new(sounds, Box::new(Capture(samples.clone())));
+        (audio, samples)
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("elma2-{}-{}.wav", name, std::process::id()))
+    }
+
+    #[test]
+    fn render_length_carries_fractions() {
+        let (mut audio, samples) = capture(sounds(100));
+        audio.render(0.0005);
+        assert_eq!(samples.borrow().len(), 0);
+        audio.render(0.0005);
+        assert_eq!(samples.borrow().len(), 1);
+
+        samples.borrow_mut().clear();
+        for _ in 0..3 {
+            audio.render(1.0 / 3.0);
+        }
+        let length = samples.borrow().len() as i32;
+        assert!((length - CAPTURE_RATE as i32).abs() <= 1, "{}", length);
+        assert!(samples.borrow().iter().all(|&sample| sample == 0.0));
+    }
+
+    #[test]
+    fn voices_are_mixed() {
+        let (mut audio, samples) = capture(sounds(100));
+        audio.play(Sound::Bump, 1.0);
+        audio.play(Sound::Apple, 0.5);
+        audio.render(0.03);
+        audio.render(0.1);
+
+        let samples = samples.borrow();
+        assert_eq!(samples.len(), 130);
+        assert_eq!(samples[0], 0.5 + 0.125);
+        assert_eq!(samples[49], 0.5 + 0.125);
+        assert_eq!(samples[50], 0.5);
+        assert_eq!(samples[99], 0.5);
+        assert_eq!(samples[100], 0.0);
+        assert!(audio.voices.is_empty());
+    }
+
+    #[test]
+    fn bursts_of_one_sound_are_merged() {
+        let (mut audio, _) = capture(sounds(1000));
+        audio.play(Sound::Bump, 0.3);
+        audio.play(Sound::Bump, 0.8);
+        audio.play(Sound::Bump, 0.5);
+        audio.play(Sound::Volt, 0.4);
+        assert_eq!(audio.voices.len(), 2);
+        assert_eq!(audio.voices[0].volume, 0.8);
+
+        // After the burst the same sound plays again.
+        audio.render(0.06);
+        audio.play(Sound::Bump, 0.2);
+        assert_eq!(audio.voices.len(), 3);
+    }
+
+    #[test]
+    fn oldest_voice_is_cut_off() {
+        let (mut audio, samples) = capture(sounds(10_000));
+        for i in 0..MAX_VOICES + 4 {
+            audio.play(Sound::Bump, i as f32);
+            audio.render(0.06);
+        }
+        assert_eq!(audio.voices.len(), MAX_VOICES);
+        assert_eq!(audio.voices[0].volume, 4.0);
+
+        // The mix holds exactly the remaining voices.
+        samples.borrow_mut().clear();
+        audio.render(0.001);
+        let expected: f32 = (4..MAX_VOICES + 4).map(|i| i as f32 * 0.5).sum();
+        assert_eq!(samples.borrow()[0], expected);
+    }
+
+    #[test]
+    fn outcome_sounds() {
+        let mut audio = Audio::new(sounds(1000), Box::new(NullOutput));
+        let moto = Moto::new(cgmath::vec2(0.0, 0.0));
+        audio.update(&moto, true, Outcome::Playing);
+        assert!(audio.voices.is_empty());
+        audio.update(&moto, true, Outcome::Dead);
+        assert_eq!(audio.voices.len(), 1);
+        assert_eq!(audio.voices[0].sound, Sound::Death);
+        assert_eq!(audio.engine_volume, 0.0);
+
+        // A new run silences what was playing.
+        audio.update(&moto, false, Outcome::Playing);
+        assert!(audio.voices.is_empty());
+        audio.update(&moto, false, Outcome::Finished);
+        assert_eq!(audio.voices[0].sound, Sound::Finish);
+    }
+
+    #[test]
+    fn synthesized_sounds_render() {
+        let mut audio = Audio::new(Sounds::synthesized(SAMPLE_RATE), Box::new(NullOutput));
+        let moto = Moto::new(cgmath::vec2(0.0, 0.0));
+        audio.update(&moto, true, Outcome::Playing);
+        for kind in [
+            EventType::Ground(0.5),
+            EventType::Apple,
+            EventType::VoltLeft,
+            EventType::VoltRight,
+        ] {
+            audio.event(kind);
+        }
+        assert_eq!(audio.voices.len(), 3);
+        audio.dorzol(0.3);
+        for _ in 0..100 {
+            audio.render(0.02);
+        }
+        assert!(audio.voices.is_empty());
+        assert!(audio.finish().is_ok());
+    }
+
+    #[test]
+    fn read_wav_averages_channels_and_resamples() {
+        let path = temp_path("read");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 22050,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for &(left, right) in &[(0, 0), (16384, 0), (16384, 16384), (-16384, -16384)] {
+            writer.write_sample(left as i16).unwrap();
+            writer.write_sample(right as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let same_rate = read_wav(&path, 22050).unwrap();
+        assert_eq!(same_rate, vec![0.0, 0.25, 0.5, -0.5]);
+
+        let doubled = read_wav(&path, 44100).unwrap();
+        assert_eq!(doubled, vec![0.0, 0.125, 0.25, 0.375, 0.5, 0.0, -0.5]);
+
+        let halved = read_wav(&path, 11025).unwrap();
+        assert_eq!(halved, vec![0.0, 0.5]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn read_wav_float() {
+        let path = temp_path("float");
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for &sample in &[0.5f32, -0.25, 1.0] {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        assert_eq!(read_wav(&path, SAMPLE_RATE).unwrap(), vec![0.5, -0.25, 1.0]);
+        fs::remove_file(&path).unwrap();
+        assert!(read_wav(&path, SAMPLE_RATE).is_err());
+    }
+
+    #[test]
+    fn wav_output_writes_the_mix() {
+        let path = temp_path("output");
+        let output = WavOutput::create(&path).unwrap();
+        let mut audio = Audio::new(sounds(SAMPLE_RATE as usize), Box::new(output));
+        audio.play(Sound::Bump, 1.0);
+        audio.play(Sound::Apple, 2.0);
+        audio.render(1.0);
+        audio.render(0.5);
+        assert!(audio.finish().is_ok());
+        // Finishing again has nothing left to write.
+        assert!(audio.finish().is_ok());
+
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().sample_rate, SAMPLE_RATE);
+        assert_eq!(reader.spec().channels, 1);
+        let samples: Vec<i16> = reader.samples().map(Result::unwrap).collect();
+        assert_eq!(samples.len(), SAMPLE_RATE as usize * 3 / 2);
+        // Clipped while both play, then the bump alone, then silence.
+        assert_eq!(samples[0], i16::MAX);
+        assert_eq!(samples[SAMPLE_RATE as usize / 2 - 1], i16::MAX);
+        assert_eq!(samples[SAMPLE_RATE as usize / 2], i16::MAX / 2);
+        assert_eq!(samples[SAMPLE_RATE as usize], 0);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn wav_output_reports_errors() {
+        assert!(WavOutput::create(Path::new("/nonexistent/dir/out.wav")).is_err());
+
+        // A device which is always full, where there is one.
+        let full = Path::new("/dev/full");
+        if !full.exists() {
+            return;
+        }
+        let mut output = WavOutput::create(full).unwrap();
+        output.write(&vec![0.5; SAMPLE_RATE as usize]);
+        let error = output.finish().unwrap_err();
+        assert!(error.starts_with("/dev/full: "), "{}", error);
+        // The error is returned once, the output is done afterwards.
+        assert!(output.finish().is_ok());
+    }
+
+    /// A flat level with the bike standing still, the only sounds are the recorded volts and turns.
+    fn export_ride(events: Vec<(f64, EventType)>) -> (Vec<f32>, usize) {
+        use elma::lev::{Object, ObjectType, Polygon};
+        use elma::rec::{Event, Frame};
+        use elma::Position;
+
+        let mut level = Level::new();
+        level.polygons = vec![Polygon {
+            grass: false,
+            vertices: vec![
+                Position::new(0.0, 0.0),
+                Position::new(100.0, 0.0),
+                Position::new(100.0, 20.0),
+                Position::new(0.0, 20.0),
+            ],
+        }];
+        level.objects = vec![
+            Object {
+                position: Position::new(50.0, 1.0),
+                object_type: ObjectType::Player,
+            },
+            Object {
+                position: Position::new(90.0, 1.0),
+                object_type: ObjectType::Exit,
+            },
+        ];
+
+        let mut ride = Ride::new();
+        let frames = 100;
+        ride.frames = (0..frames).map(|_| Frame::new()).collect();
+        for (frame, event_type) in events {
+            ride.events.push(Event {
+                time: frame * FRAME_TIME,
+                event_type,
+            });
+        }
+
+        let (mut audio, samples) = capture(Sounds {
+            volt: vec![0.1; 10],
+            turn: vec![0.4; 10],
+            ..sounds(0)
+        });
+        export(level, &ride, &mut audio).unwrap();
+        let samples = samples.borrow().clone();
+        (samples, frames)
+    }
+
+    #[test]
+    fn export_length_matches_ride() {
+        let (samples, frames) = export_ride(Vec::new());
+        let expected = frames as f64 * FRAME_TIME / TIME_SCALE * CAPTURE_RATE as f64;
+        assert!(
+            (samples.len() as f64 - expected).abs() <= 1.0,
+            "{} samples, expected {}",
+            samples.len(),
+            expected
+        );
+        assert!(samples.iter().all(|&sample| sample == 0.0));
+    }
+
+    #[test]
+    fn export_plays_events_at_their_time() {
+        let (samples, _) = export_ride(vec![
+            (20.0, EventType::VoltRight),
+            (45.5, EventType::Turn),
+            (70.0, EventType::VoltLeft),
+        ]);
+        // Frame of each event and the value of its sound.
+        let expected = [(20.0, 0.1), (45.5, 0.4), (70.0, 0.1)];
+
+        // Start of each run of a sound, they are far enough apart not to overlap.
+        let starts: Vec<(usize, f32)> = (0..samples.len())
+            .filter(|&i| samples[i] != 0.0 && (i == 0 || samples[i - 1] == 0.0))
+            .map(|i| (i, samples[i]))
+            .collect();
+        assert_eq!(starts.len(), expected.len(), "{:?}", starts);
+
+        let frame_samples = FRAME_TIME / TIME_SCALE * CAPTURE_RATE as f64;
+        for (&(frame, value), &(start, sample)) in expected.iter().zip(&starts) {
+            assert_eq!(sample, value, "frame {}", frame);
+            // Sounds of a frame start at the previous frame.
+            let time = frame * frame_samples;
+            assert!(
+                start as f64 >= time - frame_samples - 1.0 && start as f64 <= time + 1.0,
+                "frame {} at sample {}, expected about {}",
+                frame,
+                start,
+                time
+            );
+        }
+    }
+}
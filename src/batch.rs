@@ -0,0 +1,180 @@
+use crate::check::check_level;
+use crate::replay::resimulate;
+use elma::lev::Level;
+use elma::rec::Replay;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize)]
+pub struct Report {
+    pub path: String,
+    /// `level` or `replay`.
+    pub kind: &'static str,
+    pub errors: Vec<String>,
+    /// Largest bike position difference over all rides, when re-simulated. Includes divergence
+    /// from braking, which replays don't record.
+    pub max_divergence: Option<f64>,
+    pub diverged_frame: Option<usize>,
+}
+
+/// `batch <directory> [--resim] [--json]`
+pub fn run(args: &[String]) -> Result<(), String> {
+    let resim = args.iter().any(|arg| arg == "--resim");
+    let json = args.iter().any(|arg| arg == "--json");
+    let dir = args
+        .iter()
+        .find(|arg| !arg.starts_with("--"))
+        .ok_or("usage: batch <directory> [--resim] [--json]")?;
+
+    let mut files = Vec::new();
+    walk(Path::new(dir), &mut files).map_err(|e| format!("{}: {}", dir, e))?;
+    files.sort();
+
+    // Replays refer to levels by file name.
+    let levels: BTreeMap<String, &PathBuf> = files
+        .iter()
+        .filter(|path| has_extension(path, "lev"))
+        .filter_map(|path| Some((path.file_name()?.to_str()?.to_lowercase(), path)))
+        .collect();
+
+    let mut reports = Vec::new();
+    for path in &files {
+        if has_extension(path, "lev") {
+            reports.push(scan_level(path));
+        } else if has_extension(path, "rec") {
+            reports.push(scan_replay(path, &levels, resim));
+        }
+    }
+
+    if resim {
+        eprintln!(
+            "note: replays don't record the brake, rides that brake diverge regardless of physics"
+        );
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&reports).unwrap());
+    } else {
+        println!("path,kind,errors,max_divergence,diverged_frame");
+        for report in &reports {
+            println!(
+                "{},{},{},{},{}",
+                csv_field(&report.path),
+                report.kind,
+                csv_field(&report.errors.join("; ")),
+                report
+                    .max_divergence
+                    .map(|d| d.to_string())
+                    .unwrap_or_default(),
+                report
+                    .diverged_frame
+                    .map(|f| f.to_string())
+                    .unwrap_or_default(),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn scan_level(path: &Path) -> Report {
+    let errors = match Level::load(path) {
+        Ok(level) => check_level(&level)
+            .iter()
+            .map(|error| error.to_string())
+            .collect(),
+        Err(error) => vec![format!("{:?}", error)],
+    };
+
+    Report {
+        path: path.display().to_string(),
+        kind: "level",
+        errors,
+        max_divergence: None,
+        diverged_frame: None,
+    }
+}
+
+fn scan_replay(path: &Path, levels: &BTreeMap<String, &PathBuf>, resim: bool) -> Report {
+    let mut report = Report {
+        path: path.display().to_string(),
+        kind: "replay",
+        errors: Vec::new(),
+        max_divergence: None,
+        diverged_frame: None,
+    };
+
+    let replay = match Replay::load(path) {
+        Ok(replay) => replay,
+        Err(error) => {
+            report.errors.push(format!("{:?}", error));
+            return report;
+        }
+    };
+
+    if !resim {
+        return report;
+    }
+
+    let level_path = match levels.get(&replay.level.to_lowercase()) {
+        Some(level_path) => level_path,
+        None => {
+            report
+                .errors
+                .push(format!("level {} not found", replay.level));
+            return report;
+        }
+    };
+
+    for ride in &replay.rides {
+        let level = match Level::load(level_path.as_path()) {
+            Ok(level) => level,
+            Err(error) => {
+                report.errors.push(format!("{:?}", error));
+                return report;
+            }
+        };
+
+        match resimulate(level, ride) {
+            Ok(result) => {
+                let max = report.max_divergence.unwrap_or(0.0);
+                report.max_divergence = Some(max.max(result.max_divergence));
+                report.diverged_frame = match (report.diverged_frame, result.diverged_frame) {
+                    (Some(a), Some(b)) => Some(a.min(b)),
+                    (a, b) => a.or(b),
+                };
+            }
+            Err(error) => report.errors.push(error.to_string()),
+        }
+    }
+
+    report
+}
+
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            walk(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case(extension))
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
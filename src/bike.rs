@@ -1,11 +1,15 @@
 // https://github.com/Maxdamantus/recplay/blob/master/recRender.js
-use crate::physics::WHEEL_RADIUS;
+use crate::game::FRAME_TIME;
+use crate::physics::{Volt, WHEEL_RADIUS};
 use crate::scene::Scene;
 use crate::transform::Transform;
 use crate::{physics, scene};
 use cgmath::{vec2, InnerSpace, Vector2};
 use std::f64::consts::PI;
 
+const VOLT_ANIMATION: f64 = 28.0 * FRAME_TIME;
+const TURN_ANIMATION: f64 = 24.0 * FRAME_TIME;
+
 pub fn render_moto(scene: &mut Scene, scene_moto: &scene::Moto, physics_moto: &physics::Moto) {
     for i in 0..2 {
         let transform = Transform::unit()
@@ -17,18 +21,33 @@ pub fn render_moto(scene: &mut Scene, scene_moto: &scene::Moto, physics_moto: &p
         scene.set_image_pos(scene_moto.wheels[i], transform);
     }
 
-    let mut moto_transform = Transform::unit()
+    let time = physics_moto.time();
+    let turn_pos = animation_pos(time, physics_moto.last_turn(), TURN_ANIMATION);
+    let (volt_time, volt) = physics_moto.last_volt();
+    // An alt volt rotates both ways at once and the bike doesn't lean, so the arm stays put.
+    let volt_pos = match volt {
+        Volt::Both => 0.0,
+        Volt::Left | Volt::Right => animation_pos(time, volt_time, VOLT_ANIMATION),
+    };
+
+    let mut base_transform = Transform::unit()
         .translate(physics_moto.bike.position)
         .rotate(physics_moto.bike.angular_position);
 
     if physics_moto.direction {
-        moto_transform = moto_transform.scale2(vec2(-1.0, 1.0));
+        base_transform = base_transform.scale2(vec2(-1.0, 1.0));
+    }
+
+    // Squash the bike horizontally through the turn instead of mirroring it at once.
+    let mut moto_transform = base_transform;
+    if turn_pos > 0.0 {
+        moto_transform = moto_transform.scale2(vec2(-(turn_pos * PI).cos(), 1.0));
     }
 
     let suspension_transform = moto_transform.scale(1.0 / 48.0);
     let mut wheels_pos = [vec2(0.0, 0.0); 2];
     for i in 0..2 {
-        wheels_pos[i] = moto_transform
+        wheels_pos[i] = base_transform
             .inverse()
             .transform(physics_moto.wheels[i].position);
     }
@@ -48,7 +67,7 @@ pub fn render_moto(scene: &mut Scene, scene_moto: &scene::Moto, physics_moto: &p
         suspension_transform.skew(0.0, 0.5, 5.0, 6.0, vec2(9.0, -20.0), 48.0 * wheels_pos[1]),
     );
 
-    let head_pos = moto_transform
+    let head_pos = base_transform
         .inverse()
         .transform(physics_moto.head_position);
     let head_transform = moto_transform.translate(head_pos);
@@ -83,13 +102,36 @@ pub fn render_moto(scene: &mut Scene, scene_moto: &scene::Moto, physics_moto: &p
 
     let shoulder = vec2(0.0, 17.5) / 48.0;
     let handle = vec2(-64.5, 59.6) / 48.0 / 3.0 - head_pos;
-    // FIXME: animate
+    let mut hand = handle;
+    if volt_pos > 0.0 {
+        // Pull the hand in when leaning back, push it out when leaning forward.
+        let (angle, scale) = if (volt == Volt::Right) == physics_moto.direction {
+            let pos = if volt_pos >= 0.25 {
+                0.25 - 0.25 * (volt_pos - 0.25) / 0.75
+            } else {
+                volt_pos
+            };
+            (10.8 * pos, 1.0 - 1.2 * pos)
+        } else {
+            let pos = if volt_pos >= 0.2 {
+                0.2 - 0.2 * (volt_pos - 0.2) / 0.8
+            } else {
+                volt_pos
+            };
+            (-8.0 * pos, 1.0 + 0.75 * pos)
+        };
+
+        let arm = handle - shoulder;
+        let angle = arm.y.atan2(arm.x) - angle;
+        hand = shoulder + scale * arm.magnitude() * vec2(angle.cos(), angle.sin());
+    }
+
     ARM.render(
         scene,
         head_transform,
         shoulder,
         scene_moto.upper_arm,
-        handle,
+        hand,
         scene_moto.forearm,
     );
 
@@ -101,6 +143,16 @@ pub fn render_moto(scene: &mut Scene, scene_moto: &scene::Moto, physics_moto: &p
     scene.set_image_pos(scene_moto.bike, bike_transform);
 }
 
+/// Progress of an animation started at `start`, or 0 if it is not running.
+fn animation_pos(time: f64, start: f64, length: f64) -> f64 {
+    let pos = (time - start) / length;
+    if pos > 0.0 && pos < 1.0 {
+        pos
+    } else {
+        0.0
+    }
+}
+
 struct Limb {
     inner: bool,
     parts: [LimbPart; 2],
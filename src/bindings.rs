@@ -0,0 +1,184 @@
+use crate::physics::Control;
+use glutin::event::VirtualKeyCode;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+pub const BINDINGS_PATH: &str = "bindings.toml";
+
+/// Actions done once on key press.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Action {
+    Turn,
+    VoltLeft,
+    VoltRight,
+    AltVolt,
+    Restart,
+    Escape,
+    Checkpoint,
+    Respawn,
+    PreviousLevel,
+    NextLevel,
+    ZoomIn,
+    ZoomOut,
+    /// Show or hide physics telemetry.
+    Telemetry,
+    /// Pause or resume the replay being viewed.
+    ReplayPause,
+    /// Play the replay being viewed twice as fast.
+    ReplayFaster,
+    /// Play the replay being viewed half as fast.
+    ReplaySlower,
+}
+
+/// Keys of each action, any of them triggers it. Keys are named like `VirtualKeyCode`
+/// variants, for example
+///
+/// ```toml
+/// throttle = ["Up", "W"]
+/// alt_volt = ["LAlt", "RAlt"]
+/// ```
+///
+/// Missing actions keep their default keys. A key can be bound to one action only.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Bindings {
+    pub throttle: Vec<VirtualKeyCode>,
+    pub brake: Vec<VirtualKeyCode>,
+    pub volt_left: Vec<VirtualKeyCode>,
+    pub volt_right: Vec<VirtualKeyCode>,
+    /// Volt both ways at once.
+    pub alt_volt: Vec<VirtualKeyCode>,
+    pub turn: Vec<VirtualKeyCode>,
+    /// Rewind while held.
+    pub rewind: Vec<VirtualKeyCode>,
+    pub restart: Vec<VirtualKeyCode>,
+    pub escape: Vec<VirtualKeyCode>,
+    pub checkpoint: Vec<VirtualKeyCode>,
+    pub respawn: Vec<VirtualKeyCode>,
+    pub previous_level: Vec<VirtualKeyCode>,
+    pub next_level: Vec<VirtualKeyCode>,
+    pub zoom_in: Vec<VirtualKeyCode>,
+    pub zoom_out: Vec<VirtualKeyCode>,
+    pub telemetry: Vec<VirtualKeyCode>,
+    pub replay_pause: Vec<VirtualKeyCode>,
+    pub replay_faster: Vec<VirtualKeyCode>,
+    pub replay_slower: Vec<VirtualKeyCode>,
+}
+
+impl Default for Bindings {
+    fn default() -> Bindings {
+        use VirtualKeyCode::*;
+        Bindings {
+            throttle: vec![Up],
+            brake: vec![Down],
+            volt_left: vec![Left],
+            volt_right: vec![Right],
+            alt_volt: vec![LAlt, RAlt],
+            turn: vec![Space],
+            rewind: vec![Back],
+            restart: vec![R],
+            escape: vec![Escape],
+            checkpoint: vec![C],
+            respawn: vec![X],
+            previous_level: vec![PageUp],
+            next_level: vec![PageDown],
+            zoom_in: vec![Equals, Add],
+            zoom_out: vec![Minus, Subtract],
+            telemetry: vec![F3],
+            replay_pause: vec![P],
+            replay_faster: vec![Period],
+            replay_slower: vec![Comma],
+        }
+    }
+}
+
+impl Bindings {
+    pub fn load(path: &Path) -> Result<Bindings, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let bindings: Bindings =
+            toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        bindings
+            .check()
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(bindings)
+    }
+
+    /// Reject keys bound to several actions, only one of them would be triggered.
+    fn check(&self) -> Result<(), String> {
+        let all = [
+            ("throttle", &self.throttle),
+            ("brake", &self.brake),
+            ("volt_left", &self.volt_left),
+            ("volt_right", &self.volt_right),
+            ("alt_volt", &self.alt_volt),
+            ("turn", &self.turn),
+            ("rewind", &self.rewind),
+            ("restart", &self.restart),
+            ("escape", &self.escape),
+            ("checkpoint", &self.checkpoint),
+            ("respawn", &self.respawn),
+            ("previous_level", &self.previous_level),
+            ("next_level", &self.next_level),
+            ("zoom_in", &self.zoom_in),
+            ("zoom_out", &self.zoom_out),
+            ("telemetry", &self.telemetry),
+            ("replay_pause", &self.replay_pause),
+            ("replay_faster", &self.replay_faster),
+            ("replay_slower", &self.replay_slower),
+        ];
+        let mut bound = HashMap::new();
+        for &(name, keys) in &all {
+            for &key in keys {
+                match bound.insert(key, name) {
+                    Some(other) if other != name => {
+                        return Err(format!("{:?} is bound to both {} and {}", key, other, name));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Control from the keys currently held. Turns and volts are actions instead.
+    pub fn control(&self, pressed: &HashSet<VirtualKeyCode>) -> Control {
+        let held = |keys: &[VirtualKeyCode]| keys.iter().any(|key| pressed.contains(key));
+
+        Control {
+            throttle: held(&self.throttle),
+            brake: held(&self.brake),
+            ..Control::default()
+        }
+    }
+
+    pub fn rewinding(&self, pressed: &HashSet<VirtualKeyCode>) -> bool {
+        self.rewind.iter().any(|key| pressed.contains(key))
+    }
+
+    pub fn action(&self, key: VirtualKeyCode) -> Option<Action> {
+        let actions = [
+            (&self.turn, Action::Turn),
+            (&self.volt_left, Action::VoltLeft),
+            (&self.volt_right, Action::VoltRight),
+            (&self.alt_volt, Action::AltVolt),
+            (&self.restart, Action::Restart),
+            (&self.escape, Action::Escape),
+            (&self.checkpoint, Action::Checkpoint),
+            (&self.respawn, Action::Respawn),
+            (&self.previous_level, Action::PreviousLevel),
+            (&self.next_level, Action::NextLevel),
+            (&self.zoom_in, Action::ZoomIn),
+            (&self.zoom_out, Action::ZoomOut),
+            (&self.telemetry, Action::Telemetry),
+            (&self.replay_pause, Action::ReplayPause),
+            (&self.replay_faster, Action::ReplayFaster),
+            (&self.replay_slower, Action::ReplaySlower),
+        ];
+        actions
+            .iter()
+            .find(|(keys, _)| keys.contains(&key))
+            .map(|&(_, action)| action)
+    }
+}
@@ -0,0 +1,517 @@
+use cgmath::{vec2, Vector2};
+use elma::lev::{Level, ObjectType, Polygon};
+use std::fmt;
+
+pub const MAX_POLYGONS: usize = 1000;
+pub const MAX_OBJECTS: usize = 252;
+
+/// Reasons for the original game to reject a level.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LevelError {
+    NoPolygons,
+    /// Number of player starts, if not exactly one.
+    PlayerCount(usize),
+    MissingExit,
+    TooManyPolygons(usize),
+    TooManyObjects(usize),
+    TooFewVertices {
+        polygon: usize,
+    },
+    SelfIntersection {
+        polygon: usize,
+    },
+    Intersection {
+        polygons: (usize, usize),
+    },
+    /// Object is not in the air area of the level.
+    ObjectOutside {
+        object: usize,
+    },
+    /// Tessellation failed, with the polygon failing on its own if there is one.
+    Triangulation {
+        polygon: Option<usize>,
+    },
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LevelError::NoPolygons => write!(f, "level has no ground polygons"),
+            LevelError::PlayerCount(count) => {
+                write!(f, "level has {} player starts instead of one", count)
+            }
+            LevelError::MissingExit => write!(f, "level has no exit"),
+            LevelError::TooManyPolygons(count) => write!(
+                f,
+                "level has {} polygons, at most {} allowed",
+                count, MAX_POLYGONS
+            ),
+            LevelError::TooManyObjects(count) => write!(
+                f,
+                "level has {} objects, at most {} allowed",
+                count, MAX_OBJECTS
+            ),
+            LevelError::TooFewVertices { polygon } => {
+                write!(f, "polygon {} has less than three vertices", polygon)
+            }
+            LevelError::SelfIntersection { polygon } => {
+                write!(f, "polygon {} intersects itself", polygon)
+            }
+            LevelError::Intersection { polygons: (a, b) } => {
+                write!(f, "polygons {} and {} intersect", a, b)
+            }
+            LevelError::ObjectOutside { object } => {
+                write!(f, "object {} is outside of the level", object)
+            }
+            LevelError::Triangulation {
+                polygon: Some(polygon),
+            } => write!(f, "polygon {} can not be triangulated", polygon),
+            LevelError::Triangulation { polygon: None } => {
+                write!(f, "polygons can not be triangulated")
+            }
+        }
+    }
+}
+
+/// Check a level for everything the original game rejects.
+pub fn check_level(level: &Level) -> Vec<LevelError> {
+    let mut errors = Vec::new();
+
+    if level.polygons.len() > MAX_POLYGONS {
+        errors.push(LevelError::TooManyPolygons(level.polygons.len()));
+    }
+    if level.objects.len() > MAX_OBJECTS {
+        errors.push(LevelError::TooManyObjects(level.objects.len()));
+    }
+
+    let players = level.objects.iter().filter(|o| o.is_player()).count();
+    if players != 1 {
+        errors.push(LevelError::PlayerCount(players));
+    }
+    if !level
+        .objects
+        .iter()
+        .any(|o| o.object_type == ObjectType::Exit)
+    {
+        errors.push(LevelError::MissingExit);
+    }
+
+    let mut ground = Vec::new();
+    for (i, polygon) in level.polygons.iter().enumerate() {
+        if polygon.vertices.len() < 3 {
+            errors.push(LevelError::TooFewVertices { polygon: i });
+        } else if !polygon.grass {
+            ground.push(i);
+        }
+    }
+    if ground.is_empty() {
+        errors.push(LevelError::NoPolygons);
+    }
+
+    errors.extend(intersections(&level.polygons, &ground));
+
+    for (i, object) in level.objects.iter().enumerate() {
+        let position = vec2(object.position.x, object.position.y);
+        if !in_air(&level.polygons, &ground, position) {
+            errors.push(LevelError::ObjectOutside { object: i });
+        }
+    }
+
+    errors
+}
+
+struct Edge {
+    polygon: usize,
+    /// Index of the first vertex in the polygon.
+    vertex: usize,
+    a: Vector2<f64>,
+    b: Vector2<f64>,
+}
+
+/// Find intersecting edges of the given polygons, reporting every polygon or pair of polygons
+/// once.
+fn intersections(polygons: &[Polygon], indices: &[usize]) -> Vec<LevelError> {
+    let mut edges = Vec::new();
+    for &polygon in indices {
+        let vertices = &polygons[polygon].vertices;
+        for vertex in 0..vertices.len() {
+            let a = &vertices[vertex];
+            let b = &vertices[(vertex + 1) % vertices.len()];
+            edges.push(Edge {
+                polygon,
+                vertex,
+                a: vec2(a.x, a.y),
+                b: vec2(b.x, b.y),
+            });
+        }
+    }
+
+    // Sweep over edges sorted by their left end.
+    edges.sort_by(|e1, e2| e1.a.x.min(e1.b.x).partial_cmp(&e2.a.x.min(e2.b.x)).unwrap());
+
+    let mut errors = Vec::new();
+    for (i, e1) in edges.iter().enumerate() {
+        let max_x = e1.a.x.max(e1.b.x);
+        for e2 in &edges[i + 1..] {
+            if e2.a.x.min(e2.b.x) > max_x {
+                break;
+            }
+
+            if e1.polygon == e2.polygon {
+                let len = polygons[e1.polygon].vertices.len();
+                if (e1.vertex + 1) % len == e2.vertex || (e2.vertex + 1) % len == e1.vertex {
+                    continue;
+                }
+            }
+
+            if !segments_intersect(e1.a, e1.b, e2.a, e2.b) {
+                continue;
+            }
+
+            let error = if e1.polygon == e2.polygon {
+                LevelError::SelfIntersection {
+                    polygon: e1.polygon,
+                }
+            } else {
+                LevelError::Intersection {
+                    polygons: (e1.polygon.min(e2.polygon), e1.polygon.max(e2.polygon)),
+                }
+            };
+            if !errors.contains(&error) {
+                errors.push(error);
+            }
+        }
+    }
+
+    errors
+}
+
+/// Whether closed segments AB and CD have a common point.
+pub fn segments_intersect(
+    a: Vector2<f64>,
+    b: Vector2<f64>,
+    c: Vector2<f64>,
+    d: Vector2<f64>,
+) -> bool {
+    let d1 = orientation(c, d, a);
+    let d2 = orientation(c, d, b);
+    let d3 = orientation(a, b, c);
+    let d4 = orientation(a, b, d);
+
+    if ((d1 > 0.0 && d2 < 0.0) || (d1 < 0.0 && d2 > 0.0))
+        && ((d3 > 0.0 && d4 < 0.0) || (d3 < 0.0 && d4 > 0.0))
+    {
+        return true;
+    }
+
+    (d1 == 0.0 && on_segment(c, d, a))
+        || (d2 == 0.0 && on_segment(c, d, b))
+        || (d3 == 0.0 && on_segment(a, b, c))
+        || (d4 == 0.0 && on_segment(a, b, d))
+}
+
+fn orientation(a: Vector2<f64>, b: Vector2<f64>, p: Vector2<f64>) -> f64 {
+    (b - a).perp_dot(p - a)
+}
+
+/// Whether `p`, collinear with AB, lies within its bounding box.
+fn on_segment(a: Vector2<f64>, b: Vector2<f64>, p: Vector2<f64>) -> bool {
+    p.x >= a.x.min(b.x) && p.x <= a.x.max(b.x) && p.y >= a.y.min(b.y) && p.y <= a.y.max(b.y)
+}
+
+/// Air is inside an odd number of ground polygons.
+pub fn in_air(polygons: &[Polygon], indices: &[usize], point: Vector2<f64>) -> bool {
+    indices
+        .iter()
+        .filter(|&&i| inside(&polygons[i], point))
+        .count()
+        % 2
+        == 1
+}
+
+/// Even-odd point in polygon test.
+pub fn inside(polygon: &Polygon, point: Vector2<f64>) -> bool {
+    let vertices = &polygon.vertices;
+    let mut inside = false;
+    for i in 0..vertices.len() {
+        let a = &vertices[i];
+        let b = &vertices[(i + 1) % vertices.len()];
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < a.x + (b.x - a.x) * (point.y - a.y) / (b.y - a.y)
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use elma::lev::{GravityDirection, Object};
+    use elma::Position;
+
+    fn polygon(vertices: &[(f64, f64)]) -> Polygon {
+        Polygon {
+            grass: false,
+            vertices: vertices.iter().map(|&(x, y)| Position::new(x, y)).collect(),
+        }
+    }
+
+    fn square(x: f64, y: f64, size: f64) -> Polygon {
+        polygon(&[(x, y), (x + size, y), (x + size, y + size), (x, y + size)])
+    }
+
+    fn object(x: f64, y: f64, object_type: ObjectType) -> Object {
+        Object {
+            position: Position::new(x, y),
+            object_type,
+        }
+    }
+
+    fn apple(x: f64, y: f64) -> Object {
+        object(
+            x,
+            y,
+            ObjectType::Apple {
+                gravity: GravityDirection::None,
+                animation: 0,
+            },
+        )
+    }
+
+    /// A valid level: one room with a start and an exit.
+    fn level() -> Level {
+        let mut level = Level::new();
+        level.polygons = vec![square(0.0, 0.0, 10.0)];
+        level.objects = vec![
+            object(2.0, 2.0, ObjectType::Player),
+            object(8.0, 2.0, ObjectType::Exit),
+        ];
+        level
+    }
+
+    #[test]
+    fn valid_level() {
+        assert_eq!(check_level(&level()), vec![]);
+    }
+
+    #[test]
+    fn crossing_segments() {
+        let v = |x, y| vec2(x, y);
+        assert!(segments_intersect(
+            v(0.0, 0.0),
+            v(2.0, 2.0),
+            v(0.0, 2.0),
+            v(2.0, 0.0)
+        ));
+        assert!(!segments_intersect(
+            v(0.0, 0.0),
+            v(1.0, 0.0),
+            v(0.0, 1.0),
+            v(1.0, 1.0)
+        ));
+        // The second segment stops short of the first one.
+        assert!(!segments_intersect(
+            v(0.0, 0.0),
+            v(2.0, 0.0),
+            v(1.0, 1.0),
+            v(1.0, 0.1)
+        ));
+    }
+
+    #[test]
+    fn touching_segments() {
+        let v = |x, y| vec2(x, y);
+        // T junction.
+        assert!(segments_intersect(
+            v(0.0, 0.0),
+            v(2.0, 0.0),
+            v(1.0, 1.0),
+            v(1.0, 0.0)
+        ));
+        // Shared end.
+        assert!(segments_intersect(
+            v(0.0, 0.0),
+            v(1.0, 0.0),
+            v(1.0, 0.0),
+            v(1.0, 1.0)
+        ));
+        // Collinear, overlapping and apart.
+        assert!(segments_intersect(
+            v(0.0, 0.0),
+            v(2.0, 0.0),
+            v(1.0, 0.0),
+            v(3.0, 0.0)
+        ));
+        assert!(!segments_intersect(
+            v(0.0, 0.0),
+            v(1.0, 0.0),
+            v(2.0, 0.0),
+            v(3.0, 0.0)
+        ));
+    }
+
+    #[test]
+    fn adjacent_edges_are_skipped() {
+        // Every vertex is shared by two edges, including the last edge wrapping around to the
+        // first vertex.
+        let polygons = [polygon(&[
+            (0.0, 0.0),
+            (4.0, 0.0),
+            (4.0, 4.0),
+            (2.0, 1.0),
+            (0.0, 4.0),
+        ])];
+        assert_eq!(intersections(&polygons, &[0]), vec![]);
+    }
+
+    #[test]
+    fn self_intersection() {
+        let bowtie = polygon(&[(0.0, 0.0), (2.0, 2.0), (2.0, 0.0), (0.0, 2.0)]);
+        assert_eq!(
+            intersections(&[bowtie], &[0]),
+            vec![LevelError::SelfIntersection { polygon: 0 }]
+        );
+
+        // Non-adjacent edges touching at a vertex, across the wrap around.
+        let pinched = polygon(&[
+            (0.0, 0.0),
+            (4.0, 0.0),
+            (2.0, 2.0),
+            (4.0, 4.0),
+            (0.0, 4.0),
+            (2.0, 2.0),
+        ]);
+        assert_eq!(
+            intersections(&[pinched], &[0]),
+            vec![LevelError::SelfIntersection { polygon: 0 }]
+        );
+    }
+
+    #[test]
+    fn intersection_is_reported_once() {
+        let polygons = [square(0.0, 0.0, 2.0), square(1.0, 1.0, 2.0)];
+        assert_eq!(
+            intersections(&polygons, &[0, 1]),
+            vec![LevelError::Intersection { polygons: (0, 1) }]
+        );
+
+        let mut level = level();
+        level.polygons.push(square(9.0, 9.0, 2.0));
+        assert_eq!(
+            check_level(&level),
+            vec![LevelError::Intersection { polygons: (0, 1) }]
+        );
+    }
+
+    #[test]
+    fn touching_polygons_intersect() {
+        let polygons = [square(0.0, 0.0, 2.0), square(2.0, 2.0, 2.0)];
+        assert_eq!(
+            intersections(&polygons, &[0, 1]),
+            vec![LevelError::Intersection { polygons: (0, 1) }]
+        );
+    }
+
+    #[test]
+    fn nested_polygons() {
+        let polygons = [
+            square(0.0, 0.0, 10.0),
+            square(2.0, 2.0, 6.0),
+            square(4.0, 4.0, 2.0),
+        ];
+        let all = [0, 1, 2];
+        assert!(in_air(&polygons, &all, vec2(1.0, 1.0)));
+        assert!(!in_air(&polygons, &all, vec2(3.0, 3.0)));
+        assert!(in_air(&polygons, &all, vec2(5.0, 5.0)));
+        assert!(!in_air(&polygons, &all, vec2(11.0, 5.0)));
+
+        // Grass polygons are left out of the indices.
+        assert!(in_air(&polygons, &[0], vec2(3.0, 3.0)));
+    }
+
+    #[test]
+    fn object_outside() {
+        let mut level = level();
+        level.polygons.push(square(4.0, 4.0, 2.0));
+        level.objects.push(apple(5.0, 5.0));
+        level.objects.push(apple(20.0, 5.0));
+        assert_eq!(
+            check_level(&level),
+            vec![
+                LevelError::ObjectOutside { object: 2 },
+                LevelError::ObjectOutside { object: 3 },
+            ]
+        );
+    }
+
+    #[test]
+    fn player_count() {
+        let mut level = level();
+        level.objects.remove(0);
+        assert_eq!(check_level(&level), vec![LevelError::PlayerCount(0)]);
+
+        let mut level = self::level();
+        level.objects.push(object(5.0, 5.0, ObjectType::Player));
+        assert_eq!(check_level(&level), vec![LevelError::PlayerCount(2)]);
+    }
+
+    #[test]
+    fn missing_exit() {
+        let mut level = level();
+        level.objects[1] = object(8.0, 2.0, ObjectType::Killer);
+        assert_eq!(check_level(&level), vec![LevelError::MissingExit]);
+    }
+
+    #[test]
+    fn object_count() {
+        let mut level = level();
+        while level.objects.len() < MAX_OBJECTS {
+            level.objects.push(apple(5.0, 5.0));
+        }
+        assert_eq!(check_level(&level), vec![]);
+
+        level.objects.push(apple(5.0, 5.0));
+        assert_eq!(
+            check_level(&level),
+            vec![LevelError::TooManyObjects(MAX_OBJECTS + 1)]
+        );
+    }
+
+    #[test]
+    fn polygon_count() {
+        let mut level = level();
+        level.polygons = (0..=MAX_POLYGONS)
+            .map(|i| square(i as f64 * 2.0, 0.0, 1.0))
+            .collect();
+        level.objects = vec![
+            object(0.5, 0.5, ObjectType::Player),
+            object(2.5, 0.5, ObjectType::Exit),
+        ];
+        assert_eq!(
+            check_level(&level),
+            vec![LevelError::TooManyPolygons(MAX_POLYGONS + 1)]
+        );
+    }
+
+    #[test]
+    fn too_few_vertices() {
+        let mut level = level();
+        level.polygons.push(polygon(&[(20.0, 20.0), (21.0, 20.0)]));
+        assert_eq!(
+            check_level(&level),
+            vec![LevelError::TooFewVertices { polygon: 1 }]
+        );
+    }
+
+    #[test]
+    fn no_polygons() {
+        let mut level = level();
+        level.polygons[0].grass = true;
+        let errors = check_level(&level);
+        assert_eq!(errors[0], LevelError::NoPolygons);
+        // Without ground every object is outside.
+        assert_eq!(errors.len(), 3);
+    }
+}
@@ -0,0 +1,242 @@
+use crate::check::{check_level, LevelError};
+use crate::physics::{
+    Control, Events, Moto, Segments, GRAVITY, HEAD_RADIUS, OBJECT_RADIUS, WHEEL_RADIUS,
+};
+use crate::snapshot::SnapshotError;
+use cgmath::{vec2, InnerSpace, Vector2};
+use elma::lev::{GravityDirection, Level, ObjectType};
+use elma::rec::EventType;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Physics time per second of real time.
+pub const TIME_SCALE: f64 = 0.4368;
+
+/// Physics time of a 30 fps replay frame.
+pub const FRAME_TIME: f64 = TIME_SCALE / 30.0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Playing,
+    Dead,
+    Finished,
+}
+
+pub struct GameState {
+    pub moto: Moto,
+    pub level: Level,
+    pub segments: Segments,
+    /// Per level object, whether it was taken.
+    pub taken: Vec<bool>,
+    pub outcome: Outcome,
+    /// Whether the run was ever restored from a snapshot. Practice runs have no official time.
+    pub practice: bool,
+    pub checkpoint: Option<Snapshot>,
+}
+
+/// Everything that changes during a run, for save states and rewinding.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub moto: Moto,
+    pub taken: Vec<bool>,
+    pub outcome: Outcome,
+}
+
+impl GameState {
+    /// Load a level, along with the problems `check_level` found in it. The level is playable
+    /// despite them.
+    pub fn load(path: &Path) -> Result<(GameState, Vec<LevelError>), String> {
+        let level = Level::load(path).map_err(|e| format!("{}: {:?}", path.display(), e))?;
+        let errors = check_level(&level);
+        let game_state =
+            GameState::from_level(level).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok((game_state, errors))
+    }
+
+    pub fn from_level(level: Level) -> Result<GameState, LevelError> {
+        let moto = Moto::new(start(&level).ok_or(LevelError::PlayerCount(0))?);
+        let segments = Segments::new(&level.polygons)?;
+        let taken = vec![false; level.objects.len()];
+
+        Ok(GameState {
+            moto,
+            level,
+            segments,
+            taken,
+            outcome: Outcome::Playing,
+            practice: false,
+            checkpoint: None,
+        })
+    }
+
+    /// Start a new run from the player object, keeping the checkpoint.
+    pub fn restart(&mut self) {
+        self.moto = Moto::new(start(&self.level).unwrap());
+        self.taken.iter_mut().for_each(|taken| *taken = false);
+        self.outcome = Outcome::Playing;
+        self.practice = false;
+    }
+
+    pub fn advance(&mut self, control: Control, t: f64, events: &mut impl Events) {
+        let mut control = control;
+        while self.outcome == Outcome::Playing && self.moto.time() < t {
+            self.moto.step(control, &self.segments, events);
+            control = control.held();
+            self.touch_objects(events);
+
+            if self.segments.touches(self.moto.head_position, HEAD_RADIUS) {
+                self.outcome = Outcome::Dead;
+            }
+        }
+    }
+
+    fn touch_objects(&mut self, events: &mut impl Events) {
+        for i in 0..self.level.objects.len() {
+            if self.taken[i] {
+                continue;
+            }
+
+            let object = &self.level.objects[i];
+            let position = vec2(object.position.x, object.position.y);
+            let moto = &self.moto;
+            let touches = (moto.head_position - position).magnitude() < OBJECT_RADIUS + HEAD_RADIUS
+                || moto.wheels.iter().any(|wheel| {
+                    (wheel.position - position).magnitude() < OBJECT_RADIUS + WHEEL_RADIUS
+                });
+            if !touches {
+                continue;
+            }
+
+            match object.object_type {
+                ObjectType::Apple { gravity, .. } => {
+                    self.taken[i] = true;
+                    events.event(EventType::ObjectTouch(i as i16));
+                    events.event(EventType::Apple);
+
+                    let gravity = match gravity {
+                        GravityDirection::None => None,
+                        GravityDirection::Up => Some(vec2(0.0, GRAVITY)),
+                        GravityDirection::Down => Some(vec2(0.0, -GRAVITY)),
+                        GravityDirection::Left => Some(vec2(-GRAVITY, 0.0)),
+                        GravityDirection::Right => Some(vec2(GRAVITY, 0.0)),
+                    };
+                    if let Some(gravity) = gravity {
+                        self.moto.set_gravity(gravity);
+                    }
+                }
+                ObjectType::Exit => {
+                    if self.apples_left() == 0 {
+                        events.event(EventType::ObjectTouch(i as i16));
+                        self.outcome = Outcome::Finished;
+                        return;
+                    }
+                }
+                ObjectType::Killer => {
+                    events.event(EventType::ObjectTouch(i as i16));
+                    self.outcome = Outcome::Dead;
+                    return;
+                }
+                ObjectType::Player => {}
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            moto: self.moto.clone(),
+            taken: self.taken.clone(),
+            outcome: self.outcome,
+        }
+    }
+
+    /// Continue the run from `snapshot`. This makes it a practice run, so finishing it gives no
+    /// official time.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), SnapshotError> {
+        if snapshot.taken.len() != self.taken.len() {
+            return Err(SnapshotError::ObjectCount(snapshot.taken.len()));
+        }
+
+        self.moto = snapshot.moto.clone();
+        self.taken.copy_from_slice(&snapshot.taken);
+        self.outcome = snapshot.outcome;
+        self.practice = true;
+        Ok(())
+    }
+
+    pub fn set_checkpoint(&mut self) {
+        if self.outcome == Outcome::Playing {
+            self.checkpoint = Some(self.snapshot());
+        }
+    }
+
+    /// Go back to the checkpoint, if there is one.
+    pub fn respawn(&mut self) -> bool {
+        if let Some(checkpoint) = self.checkpoint.take() {
+            self.restore(&checkpoint).unwrap();
+            self.checkpoint = Some(checkpoint);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn apples_left(&self) -> usize {
+        self.level
+            .objects
+            .iter()
+            .zip(&self.taken)
+            .filter(|(object, &taken)| object.is_apple() && !taken)
+            .count()
+    }
+
+    /// Time of the run in hundredths of a second.
+    pub fn time(&self) -> i32 {
+        (self.moto.time() / TIME_SCALE * 100.0) as i32
+    }
+
+    /// Time of a finished run which counts, in hundredths.
+    pub fn official_time(&self) -> Option<i32> {
+        if self.outcome == Outcome::Finished && !self.practice {
+            Some(self.time())
+        } else {
+            None
+        }
+    }
+
+    /// Best single player time from the level's top ten, in hundredths.
+    pub fn best_time(&self) -> Option<i32> {
+        self.level
+            .best_times
+            .single
+            .first()
+            .map(|entry| entry.time.0)
+    }
+}
+
+fn start(level: &Level) -> Option<Vector2<f64>> {
+    let player = level.objects.iter().find(|object| object.is_player())?;
+    Some(vec2(player.position.x, player.position.y))
+}
+
+/// Level `offset` files away from `path` in its directory, by file name and wrapping around.
+pub fn sibling_level(path: &Path, offset: isize) -> Option<PathBuf> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+    let mut levels: Vec<PathBuf> = fs::read_dir(dir.unwrap_or_else(|| Path::new(".")))
+        .ok()?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| {
+            path.extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| e.eq_ignore_ascii_case("lev"))
+        })
+        .collect();
+    levels.sort_by_key(|path| path.to_string_lossy().to_lowercase());
+
+    let name = path.file_name()?;
+    let index = levels
+        .iter()
+        .position(|level| level.file_name() == Some(name))?;
+    let len = levels.len() as isize;
+    let index = (index as isize + offset).rem_euclid(len) as usize;
+    Some(levels.swap_remove(index))
+}
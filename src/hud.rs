@@ -0,0 +1,180 @@
+use crate::atlas::Atlas;
+use crate::game::{GameState, Outcome};
+use crate::render::PictureVertex;
+use cgmath::{vec2, InnerSpace, Vector2};
+use glutin::dpi::PhysicalSize;
+
+const TEXT_HEIGHT: f64 = 28.0;
+const MARGIN: f64 = 10.0;
+const SPACING: f64 = 4.0;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// Screen-space pictures, positioned in pixels from the bottom left corner.
+pub struct Hud {
+    pub vertices: Vec<PictureVertex>,
+    pub indices: Vec<u32>,
+    /// Show the bike's physics telemetry in the bottom left corner.
+    pub telemetry: bool,
+}
+
+impl Hud {
+    pub fn new() -> Hud {
+        Hud {
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            telemetry: false,
+        }
+    }
+
+    pub fn update(&mut self, atlas: &Atlas, game_state: &GameState, size: PhysicalSize<u32>) {
+        self.vertices.clear();
+        self.indices.clear();
+
+        let size = vec2(size.width as f64, size.height as f64);
+        let top = size.y - MARGIN;
+
+        self.text(
+            atlas,
+            &format_time(game_state.time()),
+            vec2(MARGIN, top),
+            TEXT_HEIGHT,
+            Align::Left,
+        );
+        if let Some(best_time) = game_state.best_time() {
+            self.text(
+                atlas,
+                &format_time(best_time),
+                vec2(MARGIN, top - TEXT_HEIGHT - SPACING),
+                TEXT_HEIGHT * 0.5,
+                Align::Left,
+            );
+        }
+
+        self.text(
+            atlas,
+            &game_state.apples_left().to_string(),
+            vec2(size.x - MARGIN, top),
+            TEXT_HEIGHT,
+            Align::Right,
+        );
+
+        if game_state.practice {
+            self.text(
+                atlas,
+                "PRACTICE - NOT TIMED",
+                vec2(size.x * 0.5, top),
+                TEXT_HEIGHT * 0.5,
+                Align::Center,
+            );
+        }
+
+        let message = match game_state.outcome {
+            Outcome::Playing => None,
+            Outcome::Dead => Some("YOU FAILED"),
+            Outcome::Finished if game_state.practice => Some("PRACTICE FINISHED"),
+            Outcome::Finished => Some("FINISHED"),
+        };
+        if let Some(message) = message {
+            self.text(
+                atlas,
+                message,
+                vec2(size.x * 0.5, (size.y + TEXT_HEIGHT) * 0.5),
+                TEXT_HEIGHT,
+                Align::Center,
+            );
+        }
+
+        if self.telemetry {
+            let telemetry = game_state.moto.telemetry();
+            let [force_0, force_1] = telemetry.wheel_forces;
+            let [torque_0, torque_1] = telemetry.wheel_angular_forces;
+            let lines = [
+                format!("DORZOL {:.3}", telemetry.dorzol_volume),
+                format!(
+                    "CONTACTS {} {}",
+                    telemetry.contacts[0], telemetry.contacts[1]
+                ),
+                format!(
+                    "WHEEL FORCES {:.0} {:.0}",
+                    force_0.magnitude(),
+                    force_1.magnitude()
+                ),
+                format!("WHEEL TORQUES {:.0} {:.0}", torque_0, torque_1),
+                format!("BIKE SPIN {:.2}", telemetry.bike_angular_velocity),
+            ];
+            let height = TEXT_HEIGHT * 0.5;
+            for (i, line) in lines.iter().rev().enumerate() {
+                let y = MARGIN + height + i as f64 * (height + SPACING);
+                self.text(atlas, line, vec2(MARGIN, y), height, Align::Left);
+            }
+        }
+    }
+
+    /// Lay out a line of text with its top edge at `position.y`.
+    pub fn text(
+        &mut self,
+        atlas: &Atlas,
+        text: &str,
+        position: Vector2<f64>,
+        height: f64,
+        align: Align,
+    ) {
+        let width: f64 = text
+            .chars()
+            .map(|c| glyph_width(atlas, c, height) + height * 0.2)
+            .sum::<f64>()
+            - height * 0.2;
+
+        let mut x = match align {
+            Align::Left => position.x,
+            Align::Center => position.x - width * 0.5,
+            Align::Right => position.x - width,
+        };
+
+        for c in text.chars() {
+            let advance = glyph_width(atlas, c, height);
+            if let Some(sprite) = atlas.glyphs.get(&c.to_ascii_uppercase()) {
+                let v = self.vertices.len() as u32;
+                for i in 0..4 {
+                    let (tx, ty) = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)][i];
+                    self.vertices.push(PictureVertex {
+                        position: [(x + tx * advance) as f32, (position.y - ty * height) as f32],
+                        tex_coord: [tx as f32, ty as f32],
+                        tex_bounds: sprite.bounds,
+                        mask: [-1.0, -1.0],
+                        clip: 0.0,
+                    });
+                }
+
+                self.indices
+                    .extend_from_slice(&[v, v + 1, v + 2, v, v + 2, v + 3]);
+            }
+
+            x += advance + height * 0.2;
+        }
+    }
+}
+
+fn glyph_width(atlas: &Atlas, c: char, height: f64) -> f64 {
+    match atlas.glyphs.get(&c.to_ascii_uppercase()) {
+        Some(sprite) => sprite.size.x / sprite.size.y * height,
+        None => height * 0.5,
+    }
+}
+
+/// Format hundredths of a second as `mm:ss:hh`.
+pub fn format_time(hundredths: i32) -> String {
+    let hundredths = hundredths.max(0);
+    format!(
+        "{:02}:{:02}:{:02}",
+        hundredths / 6000,
+        hundredths / 100 % 60,
+        hundredths % 100
+    )
+}
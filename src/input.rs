@@ -0,0 +1,236 @@
+use crate::bindings::{Action, Bindings};
+use crate::physics::Control;
+use glutin::event::{ElementState, VirtualKeyCode, WindowEvent};
+use std::collections::HashSet;
+
+/// Keyboard, gamepad or anything else producing controls and actions.
+pub trait InputSource {
+    fn window_event(&mut self, _event: &WindowEvent) {}
+
+    /// Read new input and push actions triggered since the last update.
+    fn update(&mut self, actions: &mut Vec<Action>);
+
+    /// Controls currently held. Turns and volts are usually pushed as actions instead.
+    fn control(&self) -> Control;
+
+    fn rewinding(&self) -> bool;
+}
+
+/// All input sources together, a control is held if any source holds it.
+pub struct Input {
+    sources: Vec<Box<dyn InputSource>>,
+}
+
+impl Input {
+    pub fn new(sources: Vec<Box<dyn InputSource>>) -> Input {
+        Input { sources }
+    }
+
+    pub fn add(&mut self, source: Box<dyn InputSource>) {
+        self.sources.push(source);
+    }
+
+    pub fn window_event(&mut self, event: &WindowEvent) {
+        for source in &mut self.sources {
+            source.window_event(event);
+        }
+    }
+
+    pub fn update(&mut self) -> Vec<Action> {
+        let mut actions = Vec::new();
+        for source in &mut self.sources {
+            source.update(&mut actions);
+        }
+        actions
+    }
+
+    pub fn control(&self) -> Control {
+        self.sources
+            .iter()
+            .map(|source| source.control())
+            .fold(Control::default(), |a, b| Control {
+                throttle: a.throttle || b.throttle,
+                brake: a.brake || b.brake,
+                turn: a.turn || b.turn,
+                volt_left: a.volt_left || b.volt_left,
+                volt_right: a.volt_right || b.volt_right,
+                alt_volt: a.alt_volt || b.alt_volt,
+            })
+    }
+
+    pub fn rewinding(&self) -> bool {
+        self.sources.iter().any(|source| source.rewinding())
+    }
+}
+
+pub struct Keyboard {
+    bindings: Bindings,
+    pressed: HashSet<VirtualKeyCode>,
+    actions: Vec<Action>,
+}
+
+impl Keyboard {
+    pub fn new(bindings: Bindings) -> Keyboard {
+        Keyboard {
+            bindings,
+            pressed: HashSet::new(),
+            actions: Vec::new(),
+        }
+    }
+}
+
+impl InputSource for Keyboard {
+    fn window_event(&mut self, event: &WindowEvent) {
+        let input = match event {
+            WindowEvent::KeyboardInput { input, .. } => input,
+            _ => return,
+        };
+        let key = match input.virtual_keycode {
+            Some(key) => key,
+            None => return,
+        };
+
+        match input.state {
+            ElementState::Pressed => {
+                // Key repeat sends presses of keys already held.
+                if self.pressed.insert(key) {
+                    self.actions.extend(self.bindings.action(key));
+                }
+            }
+            ElementState::Released => {
+                self.pressed.remove(&key);
+            }
+        }
+    }
+
+    fn update(&mut self, actions: &mut Vec<Action>) {
+        actions.append(&mut self.actions);
+    }
+
+    fn control(&self) -> Control {
+        self.bindings.control(&self.pressed)
+    }
+
+    fn rewinding(&self) -> bool {
+        self.bindings.rewinding(&self.pressed)
+    }
+}
+
+/// Input set directly, for driving the game from code.
+#[derive(Default)]
+pub struct FakeInput {
+    pub control: Control,
+    pub rewinding: bool,
+    /// Actions returned by the next update.
+    pub actions: Vec<Action>,
+}
+
+impl InputSource for FakeInput {
+    fn update(&mut self, actions: &mut Vec<Action>) {
+        actions.append(&mut self.actions);
+    }
+
+    fn control(&self) -> Control {
+        self.control
+    }
+
+    fn rewinding(&self) -> bool {
+        self.rewinding
+    }
+}
+
+/// Stick deflection below which the stick counts as centered.
+pub const STICK_DEADZONE: f32 = 0.5;
+
+/// Volts `(left, right)` from a stick's horizontal position.
+pub fn stick_volts(x: f32, deadzone: f32) -> (bool, bool) {
+    (x <= -deadzone, x >= deadzone)
+}
+
+#[cfg(feature = "gamepad")]
+pub use gamepad::Gamepads;
+
+#[cfg(feature = "gamepad")]
+mod gamepad {
+    use super::{stick_volts, InputSource, STICK_DEADZONE};
+    use crate::bindings::Action;
+    use crate::physics::Control;
+    use gilrs::{Axis, Button, EventType, Gilrs};
+
+    /// Buttons of actions done once on press.
+    const ACTIONS: [(Button, Action); 7] = [
+        (Button::East, Action::Turn),
+        (Button::DPadLeft, Action::VoltLeft),
+        (Button::DPadRight, Action::VoltRight),
+        (Button::LeftTrigger, Action::AltVolt),
+        (Button::Start, Action::Restart),
+        (Button::Select, Action::Escape),
+        (Button::North, Action::Respawn),
+    ];
+
+    /// All connected gamepads. Throttle is South or the right trigger, brake is West or the
+    /// left trigger, volts are the left stick or the d-pad and bumpers are alt volt and rewind.
+    pub struct Gamepads {
+        gilrs: Gilrs,
+        /// Stick volts `(left, right)` at the last update, a volt is done when the stick leaves
+        /// the deadzone.
+        stick: (bool, bool),
+    }
+
+    impl Gamepads {
+        pub fn new() -> Result<Gamepads, String> {
+            let gilrs = Gilrs::new().map_err(|e| e.to_string())?;
+            Ok(Gamepads {
+                gilrs,
+                stick: (false, false),
+            })
+        }
+
+        fn held(&self, button: Button) -> bool {
+            self.gilrs
+                .gamepads()
+                .any(|(_, gamepad)| gamepad.is_pressed(button))
+        }
+    }
+
+    impl InputSource for Gamepads {
+        fn update(&mut self, actions: &mut Vec<Action>) {
+            while let Some(event) = self.gilrs.next_event() {
+                if let EventType::ButtonPressed(button, _) = event.event {
+                    actions.extend(
+                        ACTIONS
+                            .iter()
+                            .filter(|&&(b, _)| b == button)
+                            .map(|&(_, action)| action),
+                    );
+                }
+            }
+
+            let mut stick = (false, false);
+            for (_, gamepad) in self.gilrs.gamepads() {
+                let (left, right) = stick_volts(gamepad.value(Axis::LeftStickX), STICK_DEADZONE);
+                stick.0 |= left;
+                stick.1 |= right;
+            }
+            if stick.0 && !self.stick.0 {
+                actions.push(Action::VoltLeft);
+            }
+            if stick.1 && !self.stick.1 {
+                actions.push(Action::VoltRight);
+            }
+            self.stick = stick;
+        }
+
+        fn control(&self) -> Control {
+            Control {
+                throttle: self.held(Button::South) || self.held(Button::RightTrigger2),
+                brake: self.held(Button::West) || self.held(Button::LeftTrigger2),
+                ..Control::default()
+            }
+        }
+
+        fn rewinding(&self) -> bool {
+            self.held(Button::RightTrigger)
+        }
+    }
+}
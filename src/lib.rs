@@ -0,0 +1,13 @@
+//! Level loading, physics, replay tools, sound and input handling which don't need a window.
+
+pub mod audio;
+pub mod batch;
+pub mod bindings;
+pub mod check;
+pub mod game;
+pub mod input;
+pub mod physics;
+pub mod replay;
+pub mod rewind;
+pub mod snapshot;
+pub mod stats;
@@ -1,18 +1,27 @@
 #![feature(bool_to_option)]
 use crate::atlas::Atlas;
-use crate::physics::{Control, Events, Moto, Segments};
+use crate::game::{sibling_level, GameState, Outcome, TIME_SCALE};
+use crate::hud::Hud;
+use crate::physics::Control;
 use crate::scene::Scene;
-use cgmath::vec2;
 use elma::lev::Level;
-use elma::rec::EventType;
+use elma::rec::Replay;
+use elma2::audio::{Audio, NullOutput, Output, Sounds, SOUNDS_PATH};
+use elma2::bindings::{Action, Bindings, BINDINGS_PATH};
+use elma2::input::{Input, Keyboard};
+use elma2::replay::Playback;
+use elma2::rewind::{Rewind, REWIND_CAPACITY};
+use elma2::{audio, batch, check, game, physics, stats};
 use gl::types::*;
-use glutin::event::{ElementState, Event, VirtualKeyCode, WindowEvent};
+use glutin::event::{Event, WindowEvent};
 use glutin::event_loop::ControlFlow;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
 mod atlas;
 mod bike;
-mod physics;
+mod font;
+mod hud;
 mod render;
 mod scene;
 mod transform;
@@ -27,49 +36,82 @@ mod gles {
     include!(concat!(env!("OUT_DIR"), "/gles_bindings.rs"));
 }*/
 
-struct GameState {
-    moto: Moto,
-    level: Level,
-    segments: Segments,
+fn load_level(path: &Path, atlas: &Atlas) -> Result<(GameState, Scene, scene::Moto), String> {
+    let (mut game_state, errors) = GameState::load(path)?;
+    for error in errors {
+        eprintln!("{}: {}", path.display(), error);
+    }
+    let mut scene = Scene::new(&mut game_state.level, atlas)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    let moto = scene.add_moto(atlas, false);
+    Ok((game_state, scene, moto))
 }
 
-impl GameState {
-    fn new(path: &str) -> GameState {
-        let level = Level::load(path).unwrap();
+/// Simulate the first ride of a replay for viewing.
+fn load_replay(level_path: &Path, replay_path: &Path) -> Result<Playback, String> {
+    let level =
+        Level::load(level_path).map_err(|e| format!("{}: {:?}", level_path.display(), e))?;
+    let replay =
+        Replay::load(replay_path).map_err(|e| format!("{}: {:?}", replay_path.display(), e))?;
+    let ride = replay
+        .rides
+        .first()
+        .ok_or_else(|| format!("{}: no rides", replay_path.display()))?;
+    Playback::new(level, ride).map_err(|e| format!("{}: {}", level_path.display(), e))
+}
 
-        let player = level
-            .objects
-            .iter()
-            .find(|object| object.is_player())
-            .unwrap();
+/// Fastest and slowest replay speeds.
+const MAX_REPLAY_SPEED: f64 = 8.0;
+const MIN_REPLAY_SPEED: f64 = 0.125;
 
-        let moto = Moto::new(vec2(player.position.x, player.position.y));
-        let segments = Segments::new(&level.polygons);
+/// View size change per zoom key press.
+const ZOOM_STEP: f64 = 1.25;
 
-        GameState {
-            moto,
-            level,
-            segments,
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let result = match args.get(1).map(String::as_str) {
+        Some("stats") => Some(stats::run(&args[2..])),
+        Some("batch") => Some(batch::run(&args[2..])),
+        Some("audio") => Some(audio::run(&args[2..])),
+        _ => None,
+    };
+    if let Some(result) = result {
+        if let Err(error) = result {
+            eprintln!("{}", error);
+            std::process::exit(1);
         }
+        return;
     }
-}
 
-struct E;
-impl Events for E {
-    fn event(&mut self, _kind: EventType) {
-        //  dbg!(kind);
-    }
-}
-
-fn main() {
-    let mut game_state = GameState::new("D:/games/Elma Online/Lev/0LP05.lev");
+    let mut level_path = PathBuf::from(
+        args.get(1)
+            .map_or("D:/games/Elma Online/Lev/0LP05.lev", String::as_str),
+    );
+    let mut atlas = Atlas::new("D:/games/ElastoMania/lgr/default.lgr");
+    let (mut game_state, mut scene, mut moto) = match load_level(&level_path, &atlas) {
+        Ok(loaded) => loaded,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+    // A replay given after the level is viewed instead of playing.
+    let mut playback = match args.get(2) {
+        Some(replay_path) => match load_replay(&level_path, Path::new(replay_path)) {
+            Ok(playback) => Some(playback),
+            Err(error) => {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    let mut replay_speed: f64 = 1.0;
+    let mut replay_paused = false;
     // dbg!(&game_state.level.objects);
     // dbg!(&game_state.level.ground);
 
-    let mut atlas = Atlas::new("D:/games/ElastoMania/lgr/default.lgr");
-    let mut scene = Scene::new(&mut game_state.level, &atlas);
-
-    let moto = scene.add_moto(&atlas, false);
+    let mut hud = Hud::new();
 
     let events_loop = glutin::event_loop::EventLoop::new();
     let window_builder = glutin::window::WindowBuilder::new()
@@ -92,10 +134,44 @@ fn main() {
     //  let _gles = gles::Gles2::load_with(|name| self.window.context().get_proc_address(name) as *const _);
 
     let mut renderer = unsafe { render::Renderer::new(&gl, &mut atlas) };
-    let time = Instant::now();
-    let mut control = Control::default();
+    let bindings = if Path::new(BINDINGS_PATH).exists() {
+        Bindings::load(Path::new(BINDINGS_PATH)).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            Bindings::default()
+        })
+    } else {
+        Bindings::default()
+    };
+    let mut input = Input::new(vec![Box::new(Keyboard::new(bindings))]);
+    #[cfg(feature = "gamepad")]
+    match elma2::input::Gamepads::new() {
+        Ok(gamepads) => input.add(Box::new(gamepads)),
+        Err(error) => eprintln!("gamepads: {}", error),
+    }
+    let output: Box<dyn Output> = {
+        #[cfg(feature = "sound")]
+        match elma2::audio::DeviceOutput::new() {
+            Ok(output) => Box::new(output),
+            Err(error) => {
+                eprintln!("sound: {}", error);
+                Box::new(NullOutput)
+            }
+        }
+        #[cfg(not(feature = "sound"))]
+        Box::new(NullOutput)
+    };
+    let sounds = Sounds::load(Path::new(SOUNDS_PATH), output.sample_rate());
+    let mut audio = Audio::new(sounds, output);
+    let mut zoom = 10.0;
     let mut next_frame_time = Instant::now();
 
+    // Physics time the game should be at, runs backwards while rewinding.
+    let mut time = 0.0;
+    let mut last_update = Instant::now();
+    let mut rewind = Rewind::new(REWIND_CAPACITY);
+    // Turns and volts pressed but not yet applied by a physics step.
+    let mut presses = Control::default();
+
     events_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::WaitUntil(next_frame_time);
         let now = Instant::now();
@@ -106,11 +182,84 @@ fn main() {
 
         let mut close = false;
         let mut resize = false;
+        let mut load = None;
+
+        if let Event::WindowEvent { event, .. } = &event {
+            input.window_event(event);
+        }
+        for action in input.update() {
+            match action {
+                Action::Turn => presses.turn = true,
+                Action::VoltLeft => presses.volt_left = true,
+                Action::VoltRight => presses.volt_right = true,
+                Action::AltVolt => presses.alt_volt = true,
+                Action::Checkpoint => game_state.set_checkpoint(),
+                Action::Respawn => {
+                    if game_state.respawn() {
+                        rewind.truncate(game_state.moto.time());
+                        time = game_state.moto.time();
+                        presses = Control::default();
+                    }
+                }
+                Action::Restart => {
+                    game_state.restart();
+                    rewind.clear();
+                    time = 0.0;
+                    presses = Control::default();
+                }
+                Action::PreviousLevel => load = sibling_level(&level_path, -1),
+                Action::NextLevel => load = sibling_level(&level_path, 1),
+                Action::ZoomIn => zoom /= ZOOM_STEP,
+                Action::ZoomOut => zoom *= ZOOM_STEP,
+                Action::Telemetry => hud.telemetry = !hud.telemetry,
+                Action::ReplayPause => replay_paused = !replay_paused,
+                Action::ReplayFaster => replay_speed = (replay_speed * 2.0).min(MAX_REPLAY_SPEED),
+                Action::ReplaySlower => replay_speed = (replay_speed * 0.5).max(MIN_REPLAY_SPEED),
+                Action::Escape => close = true,
+            }
+        }
+        let held = input.control();
+        let control = Control {
+            throttle: held.throttle,
+            brake: held.brake,
+            ..presses
+        };
+        let rewinding = input.rewinding();
 
-        let time = time.elapsed().as_secs_f64();
-        game_state
-            .moto
-            .advance(control, time * 0.4368, &game_state.segments, &mut E);
+        let real_elapsed = (now - last_update).as_secs_f64();
+        let elapsed = real_elapsed * TIME_SCALE;
+        last_update = now;
+        let mut throttle = control.throttle;
+        if let Some(playback) = &playback {
+            presses = Control::default();
+            if rewinding {
+                time -= elapsed * replay_speed;
+            } else if !replay_paused {
+                time += elapsed * replay_speed;
+            }
+            time = time.clamp(0.0, playback.duration());
+            throttle = playback.show(&mut game_state, time);
+        } else if rewinding {
+            presses = Control::default();
+            time -= elapsed;
+            if let Some(t) = rewind.rewind(&mut game_state, time) {
+                time = time.max(t);
+            }
+        } else {
+            time += elapsed;
+            let moto_time = game_state.moto.time();
+            rewind.advance(&mut game_state, control, time, &mut audio);
+            if game_state.moto.time() != moto_time {
+                presses = Control::default();
+            }
+            if game_state.outcome == Outcome::Dead && game_state.respawn() {
+                rewind.truncate(game_state.moto.time());
+            }
+            // Don't run ahead of a finished run, so rewinding starts right away.
+            time = time.min(game_state.moto.time());
+        }
+        audio.update(&game_state.moto, throttle, game_state.outcome);
+        audio.render(real_elapsed);
 
         match event {
             Event::WindowEvent {
@@ -139,22 +288,10 @@ fn main() {
                 resize = true;
             }
             Event::WindowEvent {
-                event: WindowEvent::KeyboardInput { input, .. },
+                event: WindowEvent::DroppedFile(path),
                 ..
             } => {
-                if let Some(key) = input.virtual_keycode {
-                    let state = input.state == ElementState::Pressed;
-                    match key {
-                        VirtualKeyCode::Left => control.rotate_left = state,
-                        VirtualKeyCode::Right => control.rotate_right = state,
-                        VirtualKeyCode::Up => control.throttle = state,
-                        VirtualKeyCode::Down => control.brake = state,
-                        VirtualKeyCode::Space if state => {
-                            game_state.moto.direction = !game_state.moto.direction
-                        }
-                        _ => {}
-                    }
-                }
+                load = Some(path);
             }
             Event::WindowEvent { event: _event, .. } => {
                 //    dbg!(event);
@@ -172,14 +309,15 @@ fn main() {
 
                 let viewport = render::Viewport::from_center_and_scale(
                     game_state.moto.bike.position,
-                    10.0,
+                    zoom,
                     size,
                 );
 
-                scene.animate(time);
+                scene.animate(game_state.moto.time(), &game_state.taken);
                 scene.update(viewport);
 
                 bike::render_moto(&mut scene, &moto, &game_state.moto);
+                hud.update(&atlas, &game_state, size);
 
                 unsafe {
                     renderer.draw_polygons(
@@ -189,6 +327,7 @@ fn main() {
                         viewport,
                     );
                     renderer.draw_pictures(&gl, &scene.vertices, &scene.indices, viewport);
+                    renderer.draw_hud(&gl, &hud.vertices, &hud.indices, size);
                 };
 
                 windowed_context.swap_buffers().unwrap(); // FIXME: handle error
@@ -196,6 +335,23 @@ fn main() {
             _ => {}
         };
 
+        if let Some(path) = load {
+            match load_level(&path, &atlas) {
+                Ok(loaded) => {
+                    let (new_game_state, new_scene, new_moto) = loaded;
+                    game_state = new_game_state;
+                    scene = new_scene;
+                    moto = new_moto;
+                    level_path = path;
+                    playback = None;
+                    rewind.clear();
+                    time = 0.0;
+                    presses = Control::default();
+                }
+                Err(error) => eprintln!("{}", error),
+            }
+        }
+
         if resize {
             windowed_context.resize(size);
             unsafe { gl.Viewport(0, 0, size.width as GLsizei, size.height as GLsizei) };
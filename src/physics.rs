@@ -1,6 +1,9 @@
+use crate::check::LevelError;
+use crate::snapshot::{Reader, SnapshotError, Writer};
 use cgmath::{dot, vec2, InnerSpace, Vector2};
 use elma::lev::Polygon;
 use elma::rec::EventType;
+use rayon::prelude::*;
 use std::mem;
 
 pub const HEAD_RADIUS: f64 = 0.238;
@@ -32,6 +35,8 @@ pub const HEAD_POSITION: Vector2<f64> = vec2(0.0, 0.44);
 
 pub const PI: f64 = 3.141592; // sic
 
+pub const TIME_STEP: f64 = 0.00001;
+
 const CELL_SIZE: f64 = 1.0;
 
 struct Segment {
@@ -50,13 +55,74 @@ struct Segment {
 
 pub struct Segments {
     min: Vector2<f64>,
+    max: Vector2<f64>,
     segments: Vec<Segment>,
     width: usize,
     height: usize,
-    table: Vec<Vec<u32>>,
+    /// Segments of cell `i` are `cell_segments[cell_start[i]..cell_start[i + 1]]`.
+    cell_start: Vec<u32>,
+    cell_segments: Vec<u32>,
+}
+
+/// First segment hit by a ray.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub point: Vector2<f64>,
+    /// Unit normal of the segment, facing the ray origin.
+    pub normal: Vector2<f64>,
+    pub segment: usize,
+    pub distance: f64,
 }
 
 impl Segment {
+    fn closest_point(&self, pos: Vector2<f64>) -> Vector2<f64> {
+        let fraction = dot(pos - self.a, self.dir).max(0.0).min(self.length);
+        self.a + self.dir * fraction
+    }
+
+    /// Distance along a ray with unit `dir` to the segment.
+    fn ray_distance(&self, origin: Vector2<f64>, dir: Vector2<f64>) -> Option<f64> {
+        let ab = self.dir * self.length;
+        let denom = dir.perp_dot(ab);
+        if denom == 0.0 {
+            return None;
+        }
+
+        let ao = self.a - origin;
+        let t = ao.perp_dot(ab) / denom;
+        let s = ao.perp_dot(dir) / denom;
+        if t >= 0.0 && (0.0..=1.0).contains(&s) {
+            Some(t)
+        } else {
+            None
+        }
+    }
+
+    fn intersects_box(&self, min: Vector2<f64>, max: Vector2<f64>) -> bool {
+        if self.a.x.min(self.b.x) > max.x
+            || self.a.x.max(self.b.x) < min.x
+            || self.a.y.min(self.b.y) > max.y
+            || self.a.y.max(self.b.y) < min.y
+        {
+            return false;
+        }
+
+        // Bounding boxes overlap, so the segment misses only if all corners are on one side of it.
+        let corners = [
+            vec2(min.x, min.y),
+            vec2(max.x, min.y),
+            vec2(max.x, max.y),
+            vec2(min.x, max.y),
+        ];
+        let sides = corners
+            .iter()
+            .map(|&corner| self.dir.perp_dot(corner - self.a));
+        let (low, high) = sides.fold((f64::INFINITY, -f64::INFINITY), |(low, high), side| {
+            (low.min(side), high.max(side))
+        });
+        low <= 0.0 && high >= 0.0
+    }
+
     fn collision(&self, pos: Vector2<f64>, r: f64) -> Option<Vector2<f64>> {
         let vector = pos - self.a;
         let fraction = dot(vector, self.dir);
@@ -84,140 +150,328 @@ impl Segment {
 }
 
 impl Segments {
-    pub fn new(polygons: &[Polygon]) -> Segments {
-        let mut min_x = f64::INFINITY;
-        let mut max_x = -f64::INFINITY;
-        let mut min_y = f64::INFINITY;
-        let mut max_y = -f64::INFINITY;
-
-        let mut num_segments: i32 = 0;
-        for polygon in polygons {
-            if polygon.grass {
-                continue;
-            }
-
-            num_segments -= 1;
-            for vertex in &polygon.vertices {
-                min_x = min_x.min(vertex.x);
-                min_y = min_y.min(vertex.y);
-                max_x = max_x.max(vertex.x);
-                max_y = max_y.max(vertex.y);
-                num_segments += 1;
-            }
-        }
-
-        let width = ((max_x - min_x) / CELL_SIZE).ceil() as usize + 1;
-        let height = ((max_y - min_y) / CELL_SIZE).ceil() as usize + 1;
+    pub fn new(polygons: &[Polygon]) -> Result<Segments, LevelError> {
+        let mut min = vec2(f64::INFINITY, f64::INFINITY);
+        let mut max = vec2(-f64::INFINITY, -f64::INFINITY);
+        let mut segments = Vec::new();
 
-        let mut segments = Vec::with_capacity(num_segments as usize);
-        let mut table = vec![vec![]; width * height];
-
-        for polygon in polygons {
+        for (i, polygon) in polygons.iter().enumerate() {
             if polygon.grass {
                 continue;
             }
+            if polygon.vertices.len() < 3 {
+                return Err(LevelError::TooFewVertices { polygon: i });
+            }
 
-            let mut prev = if let Some(vertex) = polygon.vertices.last() {
-                vertex.clone()
-            } else {
-                continue;
-            };
-
+            let mut prev = polygon.vertices.last().unwrap();
             for vertex in &polygon.vertices {
+                min = vec2(min.x.min(vertex.x), min.y.min(vertex.y));
+                max = vec2(max.x.max(vertex.x), max.y.max(vertex.y));
+
                 let a = vec2(prev.x, prev.y);
                 let b = vec2(vertex.x, vertex.y);
                 let ab = b - a;
                 let length = ab.magnitude();
+                prev = vertex;
+                if length == 0.0 {
+                    continue;
+                }
 
-                let index = segments.len();
                 segments.push(Segment {
                     a,
                     b,
                     dir: ab / length,
                     length,
                 });
+            }
+        }
 
-                let mut y0 = ((a.y - min_y) / CELL_SIZE).floor();
-                let mut y1 = ((b.y - min_y) / CELL_SIZE).floor();
-                if y0 > y1 {
-                    mem::swap(&mut y0, &mut y1);
-                }
+        if segments.is_empty() {
+            return Err(LevelError::NoPolygons);
+        }
 
-                let mut lower = a;
-                let mut upper = b;
-                if lower.y > upper.y {
-                    mem::swap(&mut lower, &mut upper);
-                }
+        let width = ((max.x - min.x) / CELL_SIZE).ceil() as usize + 1;
+        let height = ((max.y - min.y) / CELL_SIZE).ceil() as usize + 1;
 
-                for y in (y0 as usize)..=(y1 as usize) {
-                    // FIXME
-                    let y0 = min_y + y as f64 * CELL_SIZE;
-                    let y1 = min_y + (y as f64 + 1.0) * CELL_SIZE;
+        // Count segments per cell, turn counts into offsets and fill cells back to front.
+        let mut cell_start = vec![0; width * height + 1];
+        for segment in &segments {
+            for_each_cell(min, width, height, segment, |cell| {
+                cell_start[cell + 1] += 1
+            });
+        }
+        for cell in 0..width * height {
+            cell_start[cell + 1] += cell_start[cell];
+        }
 
-                    let lu = upper - lower;
-                    let one = if lower.y >= y0 - 0.1 {
-                        lower.x
-                    } else {
-                        lower.x + lu.x * (y0 - lower.y) / lu.y
-                    };
+        let mut cell_segments = vec![0; cell_start[width * height] as usize];
+        let mut cell_end = cell_start.clone();
+        for (index, segment) in segments.iter().enumerate() {
+            for_each_cell(min, width, height, segment, |cell| {
+                cell_segments[cell_end[cell] as usize] = index as u32;
+                cell_end[cell] += 1;
+            });
+        }
 
-                    let two = if upper.y <= y1 + 0.1 {
-                        upper.x
-                    } else {
-                        lower.x + lu.x * (y1 - lower.y) / lu.y
-                    };
+        Ok(Segments {
+            min,
+            max,
+            segments,
+            width,
+            height,
+            cell_start,
+            cell_segments,
+        })
+    }
 
-                    //  if
+    /// Replace the cells with `cells`, one list of segment indices per cell, for running the
+    /// physics on another grid layout. Only meant for comparing grids in tests and benchmarks.
+    #[doc(hidden)]
+    pub fn with_cells(mut self, cells: &[Vec<u32>]) -> Segments {
+        assert_eq!(cells.len(), self.num_cells());
+        let mut start = 0;
+        self.cell_start = vec![0];
+        for cell in cells {
+            start += cell.len() as u32;
+            self.cell_start.push(start);
+        }
+        self.cell_segments = cells.concat();
+        self
+    }
 
-                    /*    let x0 = a.x + ab.x * (y0 - a.y) / ab.y;
-                    let x1 = a.x + ab.x * (y1 - a.y) / ab.y;
-                    let x0 = ((x0 - min_x) / CELL_SIZE).floor();
-                    let x1 = ((x1 - min_x) / CELL_SIZE).floor();*/
+    fn cell(&self, pos: Vector2<f64>) -> Option<usize> {
+        let pos = (pos - self.min) / CELL_SIZE;
+        let x = pos.x.floor();
+        let y = pos.y.floor();
+        if x < 0.0 || y < 0.0 || x >= self.width as f64 || y >= self.height as f64 {
+            return None;
+        }
 
-                    let mut x0 = ((one - min_x) / CELL_SIZE).floor();
-                    let mut x1 = ((two - min_x) / CELL_SIZE).floor();
-                    if x0 > x1 {
-                        mem::swap(&mut x0, &mut x1);
-                    }
+        Some(y as usize * self.width + x as usize)
+    }
 
-                    for x in (x0 as usize)..=(x1 as usize) {
-                        table[y * width + x].push(index as u32);
-                    }
+    fn cell_segments(&self, cell: usize) -> &[u32] {
+        &self.cell_segments[self.cell_start[cell] as usize..self.cell_start[cell + 1] as usize]
+    }
+
+    /// Bounding box of ground polygons.
+    pub fn bounds(&self) -> (Vector2<f64>, Vector2<f64>) {
+        (self.min, self.max)
+    }
+
+    pub fn num_segments(&self) -> usize {
+        self.segments.len()
+    }
+
+    pub fn num_cells(&self) -> usize {
+        self.width * self.height
+    }
+
+    pub fn max_cell_len(&self) -> usize {
+        (0..self.num_cells())
+            .map(|cell| self.cell_segments(cell).len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Whether a circle touches any segment.
+    pub fn touches(&self, pos: Vector2<f64>, r: f64) -> bool {
+        let mut collisions = [vec2(0.0, 0.0); 2];
+        self.collision_test(pos, r, &mut collisions) != 0
+    }
+
+    /// Ends of a segment, as returned by queries.
+    pub fn segment(&self, index: usize) -> (Vector2<f64>, Vector2<f64>) {
+        let segment = &self.segments[index];
+        (segment.a, segment.b)
+    }
+
+    /// First segment hit by a ray within `max_distance`.
+    pub fn raycast(
+        &self,
+        origin: Vector2<f64>,
+        dir: Vector2<f64>,
+        max_distance: f64,
+    ) -> Option<RayHit> {
+        let dir = dir.normalize();
+        let start = (origin - self.min) / CELL_SIZE;
+        let size = vec2(self.width as f64, self.height as f64);
+
+        // Clip the ray to the grid.
+        let mut enter = 0.0f64;
+        let mut exit = max_distance / CELL_SIZE;
+        for axis in 0..2 {
+            if dir[axis] == 0.0 {
+                if start[axis] < 0.0 || start[axis] >= size[axis] {
+                    return None;
                 }
+                continue;
+            }
+            let t0 = (0.0 - start[axis]) / dir[axis];
+            let t1 = (size[axis] - start[axis]) / dir[axis];
+            enter = enter.max(t0.min(t1));
+            exit = exit.min(t0.max(t1));
+        }
+        if enter > exit {
+            return None;
+        }
 
-                prev = vertex.clone();
+        // Walk the cells along the ray, cell units equal world units scaled by CELL_SIZE.
+        let pos = start + dir * enter;
+        let mut cell = [0i64; 2];
+        let mut step = [0i64; 2];
+        let mut next = [f64::INFINITY; 2];
+        let mut delta = [f64::INFINITY; 2];
+        for axis in 0..2 {
+            cell[axis] = (pos[axis].floor() as i64).max(0).min(size[axis] as i64 - 1);
+            if dir[axis] > 0.0 {
+                step[axis] = 1;
+                next[axis] = (cell[axis] as f64 + 1.0 - start[axis]) / dir[axis];
+                delta[axis] = 1.0 / dir[axis];
+            } else if dir[axis] < 0.0 {
+                step[axis] = -1;
+                next[axis] = (cell[axis] as f64 - start[axis]) / dir[axis];
+                delta[axis] = -1.0 / dir[axis];
             }
         }
 
-        Segments {
-            min: vec2(min_x, min_y),
-            segments,
-            width,
-            height,
-            table,
+        let mut best: Option<(f64, usize)> = None;
+        loop {
+            let index = cell[1] as usize * self.width + cell[0] as usize;
+            for &i in self.cell_segments(index) {
+                if let Some(t) = self.segments[i as usize].ray_distance(origin, dir) {
+                    if t <= max_distance && best.is_none_or(|(best, _)| t < best) {
+                        best = Some((t, i as usize));
+                    }
+                }
+            }
+
+            let axis = if next[0] < next[1] { 0 } else { 1 };
+            let leave = next[axis].min(exit);
+            if best.is_some_and(|(t, _)| t <= leave * CELL_SIZE) || next[axis] > exit {
+                break;
+            }
+
+            cell[axis] += step[axis];
+            next[axis] += delta[axis];
+            if cell[axis] < 0 || cell[axis] >= size[axis] as i64 {
+                break;
+            }
         }
+
+        best.map(|(distance, i)| {
+            let segment = &self.segments[i];
+            let mut normal = vec2(-segment.dir.y, segment.dir.x);
+            if dot(normal, dir) > 0.0 {
+                normal = -normal;
+            }
+            RayHit {
+                point: origin + dir * distance,
+                normal,
+                segment: i,
+                distance,
+            }
+        })
     }
 
-    fn cell(&self, pos: Vector2<f64>) -> &[u32] {
-        if pos.x < self.min.x - CELL_SIZE || pos.y < self.min.y - CELL_SIZE {
-            return &[];
+    /// Closest segment within `max_distance`, with its closest point and the distance.
+    pub fn nearest(
+        &self,
+        pos: Vector2<f64>,
+        max_distance: f64,
+    ) -> Option<(usize, Vector2<f64>, f64)> {
+        let center = (pos - self.min) / CELL_SIZE;
+        let center = (center.x.floor() as i64, center.y.floor() as i64);
+        let (width, height) = (self.width as i64, self.height as i64);
+
+        // Cells in ring `k` are at least `k - 1` cells away, so stop once the best is closer.
+        let mut best: Option<(usize, Vector2<f64>, f64)> = None;
+        let max_ring = ((max_distance / CELL_SIZE).ceil() as i64).saturating_add(1);
+        let grid_ring = [center.0, width - center.0, center.1, height - center.1]
+            .iter()
+            .map(|d| d.abs())
+            .max()
+            .unwrap();
+        for k in 0..=max_ring.min(grid_ring) {
+            if best.is_some_and(|(_, _, d)| d <= (k - 1) as f64 * CELL_SIZE) {
+                break;
+            }
+
+            for y in center.1 - k..=center.1 + k {
+                if y < 0 || y >= height {
+                    continue;
+                }
+                // Only the edges of the ring are new.
+                let step = if y == center.1 - k || y == center.1 + k {
+                    1
+                } else {
+                    2 * k as usize
+                };
+                for x in (center.0 - k..=center.0 + k)
+                    .step_by(step)
+                    .filter(|&x| x >= 0 && x < width)
+                {
+                    for &i in self.cell_segments(y as usize * self.width + x as usize) {
+                        let point = self.segments[i as usize].closest_point(pos);
+                        let distance = (point - pos).magnitude();
+                        if distance <= max_distance
+                            && best.is_none_or(|(_, _, best)| distance < best)
+                        {
+                            best = Some((i as usize, point, distance));
+                        }
+                    }
+                }
+            }
         }
 
-        let pos = (pos - self.min) / CELL_SIZE;
-        let pos = vec2(pos.x.floor(), pos.y.floor());
-        if pos.x > self.width as f64 || pos.y > self.height as f64 {
-            return &[];
+        best
+    }
+
+    /// Segments intersecting a box, sorted by index.
+    pub fn in_box(&self, min: Vector2<f64>, max: Vector2<f64>) -> Vec<usize> {
+        let lower = (min - self.min) / CELL_SIZE;
+        let upper = (max - self.min) / CELL_SIZE;
+        if upper.x < 0.0
+            || upper.y < 0.0
+            || lower.x >= self.width as f64
+            || lower.y >= self.height as f64
+        {
+            return Vec::new();
         }
 
-        let x = pos.x as usize;
-        let y = pos.y as usize;
-        if x > self.width || y > self.height {
-            return &[];
+        let column = |x: f64| (x.floor().max(0.0) as usize).min(self.width - 1);
+        let row = |y: f64| (y.floor().max(0.0) as usize).min(self.height - 1);
+
+        let mut result = Vec::new();
+        for y in row(lower.y)..=row(upper.y) {
+            for x in column(lower.x)..=column(upper.x) {
+                result.extend(
+                    self.cell_segments(y * self.width + x)
+                        .iter()
+                        .map(|&i| i as usize)
+                        .filter(|&i| self.segments[i].intersects_box(min, max)),
+                );
+            }
         }
+        result.sort_unstable();
+        result.dedup();
+        result
+    }
 
-        &self.table[y * self.width + x]
+    /// Whether a point is in ground, i.e. inside an even number of polygons.
+    pub fn in_ground(&self, pos: Vector2<f64>) -> bool {
+        let crossings = self
+            .segments
+            .iter()
+            .filter(|s| {
+                (s.a.y > pos.y) != (s.b.y > pos.y)
+                    && pos.x < s.a.x + (s.b.x - s.a.x) * (pos.y - s.a.y) / (s.b.y - s.a.y)
+            })
+            .count();
+        crossings % 2 == 0
     }
 
+    /// Find up to two points where a circle with `r <= CELL_SIZE / 2` touches segments.
     fn collision_test(
         &self,
         pos: Vector2<f64>,
@@ -231,9 +485,16 @@ impl Segments {
             pos + vec2(-r, r),
         ];
 
+        // Corners of the circle's bounding box cover every cell it overlaps. A cell under several
+        // corners is scanned again for each of them like in the original, which affects how
+        // collision points get averaged.
         let mut collision = false;
         for &corner in &corners {
-            for &i in self.cell(corner) {
+            let cell = match self.cell(corner) {
+                Some(cell) => cell,
+                None => continue,
+            };
+            for &i in self.cell_segments(cell) {
                 let segment = &self.segments[i as usize];
                 if let Some(point) = segment.collision(pos, r) {
                     if !collision {
@@ -256,6 +517,44 @@ impl Segments {
     }
 }
 
+/// Call `f` with every cell the segment passes through, including cells it only touches.
+fn for_each_cell(
+    min: Vector2<f64>,
+    width: usize,
+    height: usize,
+    segment: &Segment,
+    mut f: impl FnMut(usize),
+) {
+    let mut lower = (segment.a - min) / CELL_SIZE;
+    let mut upper = (segment.b - min) / CELL_SIZE;
+    if lower.y > upper.y {
+        mem::swap(&mut lower, &mut upper);
+    }
+
+    let row = |y: f64| (y.floor().max(0.0) as usize).min(height - 1);
+    let column = |x: f64| (x.floor().max(0.0) as usize).min(width - 1);
+
+    for y in row(lower.y)..=row(upper.y) {
+        // Part of the segment within the row.
+        let y0 = lower.y.max(y as f64);
+        let y1 = upper.y.min(y as f64 + 1.0);
+        let (x0, x1) = if upper.y == lower.y {
+            (lower.x, upper.x)
+        } else {
+            let slope = (upper.x - lower.x) / (upper.y - lower.y);
+            (
+                lower.x + slope * (y0 - lower.y),
+                lower.x + slope * (y1 - lower.y),
+            )
+        };
+
+        for x in column(x0.min(x1))..=column(x0.max(x1)) {
+            f(y * width + x);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Moto {
     pub wheels: [Object; 2],
     pub bike: Object,
@@ -269,10 +568,71 @@ pub struct Moto {
     brake_da: [f64; 2],
     rotation_time: f64,
     rotation_angular_velocity: f64,
+    volt: Volt,
+    turn_time: f64,
     gravity: Vector2<f64>,
     time: f64,
+    telemetry: Telemetry,
 }
 
+/// Compares the simulation state only, telemetry is an output of the last step.
+impl PartialEq for Moto {
+    fn eq(&self, other: &Moto) -> bool {
+        self.wheels == other.wheels
+            && self.bike == other.bike
+            && self.head_position == other.head_position
+            && self.head_velocity == other.head_velocity
+            && self.braking == other.braking
+            && self.direction == other.direction
+            && self.rotation_left == other.rotation_left
+            && self.rotation_right == other.rotation_right
+            && self.eaten_apples == other.eaten_apples
+            && self.brake_da == other.brake_da
+            && self.rotation_time == other.rotation_time
+            && self.rotation_angular_velocity == other.rotation_angular_velocity
+            && self.volt == other.volt
+            && self.turn_time == other.turn_time
+            && self.gravity == other.gravity
+            && self.time == other.time
+    }
+}
+
+/// Values computed during the last step, for debugging and analysis. Recording them doesn't
+/// change the simulation. Not saved in snapshots.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Telemetry {
+    /// How hard the bike body is pushed onto the wheel springs, see `Events::dorzol`.
+    pub dorzol_volume: f64,
+    /// Ground points each wheel was touching, 0 to 2.
+    pub contacts: [u8; 2],
+    /// Force of the bike on each wheel.
+    pub wheel_forces: [Vector2<f64>; 2],
+    /// Throttle and brake torque on each wheel.
+    pub wheel_angular_forces: [f64; 2],
+    pub bike_angular_velocity: f64,
+}
+
+impl Default for Telemetry {
+    fn default() -> Telemetry {
+        Telemetry {
+            dorzol_volume: 0.0,
+            contacts: [0, 0],
+            wheel_forces: [vec2(0.0, 0.0), vec2(0.0, 0.0)],
+            wheel_angular_forces: [0.0, 0.0],
+            bike_angular_velocity: 0.0,
+        }
+    }
+}
+
+/// Direction of a volt. An alt volt rotates both ways at once.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Volt {
+    Left,
+    Right,
+    Both,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Object {
     pub position: Vector2<f64>,
     velocity: Vector2<f64>,
@@ -282,10 +642,27 @@ pub struct Object {
 
 #[derive(Debug, Copy, Clone, Default)]
 pub struct Control {
-    pub rotate_left: bool,
-    pub rotate_right: bool,
     pub throttle: bool,
     pub brake: bool,
+    /// Turn around. Applies to the first step only when advancing several steps.
+    pub turn: bool,
+    /// Volts, applied to the first step like `turn`. Ignored until `ROTATION_PERIOD` has passed
+    /// since the last volt, so a volt needs a new press after that.
+    pub volt_left: bool,
+    pub volt_right: bool,
+    /// Volt both ways at once.
+    pub alt_volt: bool,
+}
+
+impl Control {
+    /// Only the held inputs, without presses applied to a single step.
+    pub fn held(self) -> Control {
+        Control {
+            throttle: self.throttle,
+            brake: self.brake,
+            ..Control::default()
+        }
+    }
 }
 
 impl Moto {
@@ -324,7 +701,10 @@ impl Moto {
             rotation_right: false,
             rotation_time: -100.0,
             rotation_angular_velocity: 0.0,
+            volt: Volt::Left,
+            turn_time: -100.0,
             gravity: vec2(0.0, -GRAVITY),
+            telemetry: Telemetry::default(),
         }
     }
 
@@ -335,16 +715,314 @@ impl Moto {
         segments: &Segments,
         events: &mut impl Events,
     ) {
-        let dt = 0.00001;
+        let mut control = control;
         while self.time < t {
-            self.time += dt;
-            advance(self, control, self.time, dt, segments, events);
+            self.step(control, segments, events);
+            control = control.held();
+        }
+    }
+
+    /// Advance simulation by a single `TIME_STEP`.
+    pub fn step(&mut self, control: Control, segments: &Segments, events: &mut impl Events) {
+        self.time += TIME_STEP;
+        advance(self, control, self.time, TIME_STEP, segments, events);
+    }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Time and direction of the last volt.
+    pub fn last_volt(&self) -> (f64, Volt) {
+        (self.rotation_time, self.volt)
+    }
+
+    pub fn last_turn(&self) -> f64 {
+        self.turn_time
+    }
+
+    pub fn telemetry(&self) -> &Telemetry {
+        &self.telemetry
+    }
+
+    pub fn set_gravity(&mut self, gravity: Vector2<f64>) {
+        self.gravity = gravity;
+    }
+
+    pub(crate) fn write(&self, writer: &mut Writer) {
+        writer.f64(self.time);
+        for object in self.wheels.iter().chain(Some(&self.bike)) {
+            writer.f64(object.position.x);
+            writer.f64(object.position.y);
+            writer.f64(object.velocity.x);
+            writer.f64(object.velocity.y);
+            writer.f64(object.angular_position);
+            writer.f64(object.angular_velocity);
+        }
+        writer.f64(self.head_position.x);
+        writer.f64(self.head_position.y);
+        writer.f64(self.head_velocity.x);
+        writer.f64(self.head_velocity.y);
+        writer.bool(self.braking);
+        writer.bool(self.direction);
+        writer.bool(self.rotation_left);
+        writer.bool(self.rotation_right);
+        writer.f64(self.brake_da[0]);
+        writer.f64(self.brake_da[1]);
+        writer.f64(self.rotation_time);
+        writer.f64(self.rotation_angular_velocity);
+        writer.0.push(match self.volt {
+            Volt::Left => 0,
+            Volt::Right => 1,
+            Volt::Both => 2,
+        });
+        writer.f64(self.turn_time);
+        writer.f64(self.gravity.x);
+        writer.f64(self.gravity.y);
+    }
+
+    pub(crate) fn read(reader: &mut Reader) -> Result<Moto, SnapshotError> {
+        let time = reader.f64()?;
+        let mut objects = Vec::with_capacity(3);
+        for _ in 0..3 {
+            objects.push(Object {
+                position: vec2(reader.f64()?, reader.f64()?),
+                velocity: vec2(reader.f64()?, reader.f64()?),
+                angular_position: reader.f64()?,
+                angular_velocity: reader.f64()?,
+            });
+        }
+        let bike = objects.pop().unwrap();
+        let wheel_1 = objects.pop().unwrap();
+        let wheel_0 = objects.pop().unwrap();
+
+        Ok(Moto {
+            time,
+            wheels: [wheel_0, wheel_1],
+            bike,
+            head_position: vec2(reader.f64()?, reader.f64()?),
+            head_velocity: vec2(reader.f64()?, reader.f64()?),
+            braking: reader.bool()?,
+            direction: reader.bool()?,
+            rotation_left: reader.bool()?,
+            rotation_right: reader.bool()?,
+            eaten_apples: 0,
+            brake_da: [reader.f64()?, reader.f64()?],
+            rotation_time: reader.f64()?,
+            rotation_angular_velocity: reader.f64()?,
+            volt: match reader.u8()? {
+                0 => Volt::Left,
+                1 => Volt::Right,
+                2 => Volt::Both,
+                _ => return Err(SnapshotError::BadValue),
+            },
+            turn_time: reader.f64()?,
+            gravity: vec2(reader.f64()?, reader.f64()?),
+            telemetry: Telemetry::default(),
+        })
+    }
+}
+
+/// Independent bikes on the same ground, advanced in parallel.
+///
+/// State is stored struct-of-arrays, one column per `Moto` field. To advance, each bike is
+/// gathered into a `Moto`, stepped by the same code as `Moto::step` and scattered back, so results
+/// are identical to advancing them one by one. Bikes are spread over threads with rayon, there is
+/// no SIMD.
+pub struct MotoBatch {
+    wheels: [Objects; 2],
+    bike: Objects,
+    head_position: Vec<Vector2<f64>>,
+    head_velocity: Vec<Vector2<f64>>,
+    braking: Vec<bool>,
+    direction: Vec<bool>,
+    rotation_left: Vec<bool>,
+    rotation_right: Vec<bool>,
+    eaten_apples: Vec<i32>,
+    brake_da: Vec<[f64; 2]>,
+    rotation_time: Vec<f64>,
+    rotation_angular_velocity: Vec<f64>,
+    volt: Vec<Volt>,
+    turn_time: Vec<f64>,
+    gravity: Vec<Vector2<f64>>,
+    time: Vec<f64>,
+    telemetry: Vec<Telemetry>,
+    /// Control of each bike, used until changed.
+    pub controls: Vec<Control>,
+}
+
+/// Columns of `Object` fields.
+#[derive(Default)]
+struct Objects {
+    position: Vec<Vector2<f64>>,
+    velocity: Vec<Vector2<f64>>,
+    angular_position: Vec<f64>,
+    angular_velocity: Vec<f64>,
+}
+
+impl Objects {
+    fn get(&self, i: usize) -> Object {
+        Object {
+            position: self.position[i],
+            velocity: self.velocity[i],
+            angular_position: self.angular_position[i],
+            angular_velocity: self.angular_velocity[i],
+        }
+    }
+
+    fn set(&mut self, i: usize, object: &Object) {
+        self.position[i] = object.position;
+        self.velocity[i] = object.velocity;
+        self.angular_position[i] = object.angular_position;
+        self.angular_velocity[i] = object.angular_velocity;
+    }
+
+    fn push(&mut self, object: &Object) {
+        self.position.push(object.position);
+        self.velocity.push(object.velocity);
+        self.angular_position.push(object.angular_position);
+        self.angular_velocity.push(object.angular_velocity);
+    }
+}
+
+impl MotoBatch {
+    pub fn new(positions: &[Vector2<f64>]) -> MotoBatch {
+        let mut batch = MotoBatch {
+            wheels: Default::default(),
+            bike: Objects::default(),
+            head_position: Vec::new(),
+            head_velocity: Vec::new(),
+            braking: Vec::new(),
+            direction: Vec::new(),
+            rotation_left: Vec::new(),
+            rotation_right: Vec::new(),
+            eaten_apples: Vec::new(),
+            brake_da: Vec::new(),
+            rotation_time: Vec::new(),
+            rotation_angular_velocity: Vec::new(),
+            volt: Vec::new(),
+            turn_time: Vec::new(),
+            gravity: Vec::new(),
+            time: Vec::new(),
+            telemetry: Vec::new(),
+            controls: Vec::new(),
+        };
+        for &position in positions {
+            batch.push(&Moto::new(position));
+        }
+        batch
+    }
+
+    /// Add a bike with no control.
+    pub fn push(&mut self, moto: &Moto) {
+        self.wheels[0].push(&moto.wheels[0]);
+        self.wheels[1].push(&moto.wheels[1]);
+        self.bike.push(&moto.bike);
+        self.head_position.push(moto.head_position);
+        self.head_velocity.push(moto.head_velocity);
+        self.braking.push(moto.braking);
+        self.direction.push(moto.direction);
+        self.rotation_left.push(moto.rotation_left);
+        self.rotation_right.push(moto.rotation_right);
+        self.eaten_apples.push(moto.eaten_apples);
+        self.brake_da.push(moto.brake_da);
+        self.rotation_time.push(moto.rotation_time);
+        self.rotation_angular_velocity
+            .push(moto.rotation_angular_velocity);
+        self.volt.push(moto.volt);
+        self.turn_time.push(moto.turn_time);
+        self.gravity.push(moto.gravity);
+        self.time.push(moto.time);
+        self.telemetry.push(moto.telemetry);
+        self.controls.push(Control::default());
+    }
+
+    /// Copy of bike `i`.
+    pub fn moto(&self, i: usize) -> Moto {
+        Moto {
+            wheels: [self.wheels[0].get(i), self.wheels[1].get(i)],
+            bike: self.bike.get(i),
+            head_position: self.head_position[i],
+            head_velocity: self.head_velocity[i],
+            braking: self.braking[i],
+            direction: self.direction[i],
+            rotation_left: self.rotation_left[i],
+            rotation_right: self.rotation_right[i],
+            eaten_apples: self.eaten_apples[i],
+            brake_da: self.brake_da[i],
+            rotation_time: self.rotation_time[i],
+            rotation_angular_velocity: self.rotation_angular_velocity[i],
+            volt: self.volt[i],
+            turn_time: self.turn_time[i],
+            gravity: self.gravity[i],
+            time: self.time[i],
+            telemetry: self.telemetry[i],
+        }
+    }
+
+    /// Replace bike `i`.
+    pub fn set_moto(&mut self, i: usize, moto: &Moto) {
+        self.wheels[0].set(i, &moto.wheels[0]);
+        self.wheels[1].set(i, &moto.wheels[1]);
+        self.bike.set(i, &moto.bike);
+        self.head_position[i] = moto.head_position;
+        self.head_velocity[i] = moto.head_velocity;
+        self.braking[i] = moto.braking;
+        self.direction[i] = moto.direction;
+        self.rotation_left[i] = moto.rotation_left;
+        self.rotation_right[i] = moto.rotation_right;
+        self.eaten_apples[i] = moto.eaten_apples;
+        self.brake_da[i] = moto.brake_da;
+        self.rotation_time[i] = moto.rotation_time;
+        self.rotation_angular_velocity[i] = moto.rotation_angular_velocity;
+        self.volt[i] = moto.volt;
+        self.turn_time[i] = moto.turn_time;
+        self.gravity[i] = moto.gravity;
+        self.time[i] = moto.time;
+        self.telemetry[i] = moto.telemetry;
+    }
+
+    /// Bike body positions, without gathering whole bikes.
+    pub fn positions(&self) -> &[Vector2<f64>] {
+        &self.bike.position
+    }
+
+    pub fn len(&self) -> usize {
+        self.time.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.time.is_empty()
+    }
+
+    /// Advance every bike to time `t` with its control, like `Moto::advance`. Turns and volts are
+    /// cleared afterwards.
+    pub fn advance(&mut self, t: f64, segments: &Segments) {
+        let motos: Vec<Moto> = (0..self.len())
+            .into_par_iter()
+            .map(|i| {
+                let mut moto = self.moto(i);
+                moto.advance(self.controls[i], t, segments, &mut ());
+                moto
+            })
+            .collect();
+        for (i, moto) in motos.iter().enumerate() {
+            self.set_moto(i, moto);
+            self.controls[i] = self.controls[i].held();
         }
     }
 }
 
 pub trait Events {
     fn event(&mut self, kind: EventType);
+
+    /// Called every step with how hard the bike body is pushed onto the wheel springs, zero when
+    /// they are not compressed. The original game used this for the suspension sound.
+    fn dorzol(&mut self, _volume: f64) {}
+}
+
+impl Events for () {
+    fn event(&mut self, _kind: EventType) {}
 }
 
 fn advance(
@@ -355,17 +1033,26 @@ fn advance(
     segments: &Segments,
     events: &mut impl Events,
 ) {
+    // The head is moved to the other side by `compute_head_pos` below.
+    if control.turn {
+        moto.direction = !moto.direction;
+        moto.turn_time = t;
+        events.event(EventType::Turn);
+    }
+
     let mut rotate_left = false;
     let mut rotate_right = false;
 
     if moto.rotation_time + ROTATION_PERIOD < t {
-        if control.rotate_right {
+        if control.volt_right || control.alt_volt {
             moto.rotation_time = t;
+            moto.volt = Volt::Right;
             rotate_right = true;
             events.event(EventType::VoltRight);
         }
-        if control.rotate_left {
+        if control.volt_left || control.alt_volt {
             moto.rotation_time = t;
+            moto.volt = if rotate_right { Volt::Both } else { Volt::Left };
             rotate_left = true;
             events.event(EventType::VoltLeft);
         }
@@ -557,6 +1244,8 @@ fn advance(
             num_collisions -= 1;
         }
 
+        moto.telemetry.contacts[i] = num_collisions as u8;
+
         if num_collisions == 2 {
             moto.wheels[i].velocity = vec2(0.0, 0.0);
             moto.wheels[i].angular_velocity = 0.0;
@@ -587,9 +1276,19 @@ fn advance(
     moto.bike.angular_position += moto.bike.angular_velocity * dt;
     moto.bike.velocity += (bike_force * (1.0 / BIKE_MASS) + moto.gravity) * dt;
     moto.bike.position += moto.bike.velocity * dt;
+
+    moto.telemetry.dorzol_volume = dorzol_volume;
+    moto.telemetry.wheel_forces = wheel_forces;
+    moto.telemetry.wheel_angular_forces = wheel_angular_forces;
+    moto.telemetry.bike_angular_velocity = moto.bike.angular_velocity;
+    events.dorzol(dorzol_volume);
 }
 
 impl Object {
+    pub fn angular_velocity(&self) -> f64 {
+        self.angular_velocity
+    }
+
     fn push_out(&mut self, collision: Vector2<f64>) {
         let vector = self.position - collision;
         let dist = vector.magnitude();
@@ -741,3 +1440,392 @@ fn fix_angle(a: &mut f64) {
         *a -= 2.0 * PI;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use elma::Position;
+
+    /// Deterministic numbers in `[0, 1)`.
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> f64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 >> 11) as f64 / (1u64 << 53) as f64
+        }
+
+        fn range(&mut self, low: f64, high: f64) -> f64 {
+            low + (high - low) * self.next()
+        }
+
+        fn point(&mut self, min: Vector2<f64>, max: Vector2<f64>) -> Vector2<f64> {
+            vec2(self.range(min.x, max.x), self.range(min.y, max.y))
+        }
+    }
+
+    fn polygon(vertices: &[(f64, f64)]) -> Polygon {
+        Polygon {
+            grass: false,
+            vertices: vertices.iter().map(|&(x, y)| Position::new(x, y)).collect(),
+        }
+    }
+
+    fn square(x: f64, y: f64, size: f64) -> Polygon {
+        polygon(&[(x, y), (x + size, y), (x + size, y + size), (x, y + size)])
+    }
+
+    /// A bumpy outer polygon around three nested squares and a triangle, plus grass which
+    /// queries ignore.
+    fn polygons() -> Vec<Polygon> {
+        let outer = (0..60)
+            .map(|i| {
+                let angle = i as f64 / 60.0 * 2.0 * std::f64::consts::PI;
+                let radius = 30.0 + 3.0 * (angle * 7.0).sin();
+                (40.3 + radius * angle.cos(), 20.7 + radius * angle.sin())
+            })
+            .collect::<Vec<_>>();
+        let mut grass = square(35.0, 30.0, 4.0);
+        grass.grass = true;
+        vec![
+            polygon(&outer),
+            square(30.1, 10.2, 20.0),
+            square(35.1, 15.2, 10.0),
+            square(38.1, 18.2, 4.0),
+            polygon(&[(20.5, 30.5), (27.5, 31.5), (22.5, 38.5)]),
+            grass,
+        ]
+    }
+
+    fn segments() -> (Vec<Polygon>, Segments) {
+        let polygons = polygons();
+        let segments = Segments::new(&polygons).unwrap();
+        (polygons, segments)
+    }
+
+    fn all_segments(segments: &Segments) -> Vec<(Vector2<f64>, Vector2<f64>)> {
+        (0..segments.num_segments())
+            .map(|i| segments.segment(i))
+            .collect()
+    }
+
+    /// Area around the level, reaching well outside the grid.
+    fn surroundings(segments: &Segments) -> (Vector2<f64>, Vector2<f64>) {
+        let (min, max) = segments.bounds();
+        (min - vec2(25.0, 25.0), max + vec2(25.0, 25.0))
+    }
+
+    fn brute_raycast(
+        segments: &Segments,
+        origin: Vector2<f64>,
+        dir: Vector2<f64>,
+        max_distance: f64,
+    ) -> Option<f64> {
+        let dir = dir.normalize();
+        all_segments(segments)
+            .into_iter()
+            .filter_map(|(a, b)| {
+                // Solve origin + t * dir = a + s * (b - a).
+                let e = b - a;
+                let det = e.x * dir.y - e.y * dir.x;
+                if det == 0.0 {
+                    return None;
+                }
+                let d = a - origin;
+                let t = (e.x * d.y - e.y * d.x) / det;
+                let s = (dir.x * d.y - dir.y * d.x) / det;
+                if t >= 0.0 && t <= max_distance && s >= 0.0 && s <= 1.0 {
+                    Some(t)
+                } else {
+                    None
+                }
+            })
+            .fold(None, |best: Option<f64>, t| {
+                Some(best.map_or(t, |b| b.min(t)))
+            })
+    }
+
+    fn assert_raycast(segments: &Segments, origin: Vector2<f64>, dir: Vector2<f64>, max: f64) {
+        let hit = segments.raycast(origin, dir, max);
+        let expected = brute_raycast(segments, origin, dir, max);
+        match (hit, expected) {
+            (None, None) => {}
+            (Some(hit), Some(distance)) => {
+                assert!(
+                    (hit.distance - distance).abs() < 1e-9,
+                    "{:?} {:?} {}: {:?} != {}",
+                    origin,
+                    dir,
+                    max,
+                    hit,
+                    distance
+                );
+                let (a, b) = segments.segment(hit.segment);
+                let ab = b - a;
+                assert!(ab.perp_dot(hit.point - a).abs() < 1e-9 * ab.magnitude());
+                assert!((hit.normal.magnitude() - 1.0).abs() < 1e-12);
+                assert!(dot(hit.normal, dir) <= 0.0);
+            }
+            (hit, expected) => panic!(
+                "{:?} {:?} {}: {:?} != {:?}",
+                origin, dir, max, hit, expected
+            ),
+        }
+    }
+
+    #[test]
+    fn raycast_matches_brute_force() {
+        let (_, segments) = segments();
+        let (low, high) = surroundings(&segments);
+        let mut random = Random(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..2000 {
+            let origin = random.point(low, high);
+            let angle = random.range(0.0, 2.0 * std::f64::consts::PI);
+            let dir = vec2(angle.cos(), angle.sin());
+            let max = if random.next() < 0.25 {
+                f64::INFINITY
+            } else {
+                random.range(0.0, 60.0)
+            };
+            assert_raycast(&segments, origin, dir, max);
+        }
+    }
+
+    #[test]
+    fn raycast_from_outside_grid() {
+        let (_, segments) = segments();
+        let (min, max) = segments.bounds();
+        let center = (min + max) / 2.0;
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+        for _ in 0..500 {
+            let angle = random.range(0.0, 2.0 * std::f64::consts::PI);
+            let origin = center + vec2(angle.cos(), angle.sin()) * random.range(60.0, 300.0);
+            // Roughly towards the level, sometimes missing it.
+            let target = random.point(min - vec2(10.0, 10.0), max + vec2(10.0, 10.0));
+            assert_raycast(&segments, origin, target - origin, f64::INFINITY);
+            assert!(segments
+                .raycast(origin, origin - center, f64::INFINITY)
+                .is_none());
+        }
+    }
+
+    #[test]
+    fn raycast_axis_aligned() {
+        let (_, segments) = segments();
+        let (low, high) = surroundings(&segments);
+        let mut random = Random(0x1234_5678_9abc_def1);
+        let dirs = [
+            vec2(1.0, 0.0),
+            vec2(-1.0, 0.0),
+            vec2(0.0, 1.0),
+            vec2(0.0, -1.0),
+        ];
+        for _ in 0..500 {
+            let origin = random.point(low, high);
+            for &dir in &dirs {
+                assert_raycast(&segments, origin, dir, f64::INFINITY);
+                assert_raycast(&segments, origin, dir, random.range(0.0, 40.0));
+            }
+        }
+    }
+
+    #[test]
+    fn raycast_max_distance() {
+        let (_, segments) = segments();
+        let origin = vec2(40.3, 20.7);
+        let mut random = Random(0x0bad_cafe_dead_beef);
+        for _ in 0..200 {
+            let angle = random.range(0.0, 2.0 * std::f64::consts::PI);
+            let dir = vec2(angle.cos(), angle.sin());
+            let distance = brute_raycast(&segments, origin, dir, f64::INFINITY).unwrap();
+            assert!(segments.raycast(origin, dir, distance - 1e-6).is_none());
+            let hit = segments.raycast(origin, dir, distance + 1e-6).unwrap();
+            assert!((hit.distance - distance).abs() < 1e-9);
+        }
+    }
+
+    fn brute_nearest(segments: &Segments, pos: Vector2<f64>, max_distance: f64) -> Option<f64> {
+        all_segments(segments)
+            .into_iter()
+            .map(|(a, b)| {
+                let ab = b - a;
+                let t = (dot(pos - a, ab) / ab.magnitude2()).max(0.0).min(1.0);
+                (a + ab * t - pos).magnitude()
+            })
+            .filter(|&distance| distance <= max_distance)
+            .fold(None, |best: Option<f64>, d| {
+                Some(best.map_or(d, |b| b.min(d)))
+            })
+    }
+
+    fn assert_nearest(segments: &Segments, pos: Vector2<f64>, max: f64) {
+        let nearest = segments.nearest(pos, max);
+        let expected = brute_nearest(segments, pos, max);
+        match (nearest, expected) {
+            (None, None) => {}
+            (Some((i, point, distance)), Some(expected)) => {
+                assert!(
+                    (distance - expected).abs() < 1e-9,
+                    "{:?} {}: {} != {}",
+                    pos,
+                    max,
+                    distance,
+                    expected
+                );
+                assert!(((point - pos).magnitude() - distance).abs() < 1e-9);
+                let (a, b) = segments.segment(i);
+                let ab = b - a;
+                assert!(ab.perp_dot(point - a).abs() < 1e-9 * ab.magnitude());
+            }
+            (nearest, expected) => {
+                panic!("{:?} {}: {:?} != {:?}", pos, max, nearest, expected)
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_matches_brute_force() {
+        let (_, segments) = segments();
+        let (low, high) = surroundings(&segments);
+        let mut random = Random(0xdead_beef_0123_4567);
+        for _ in 0..2000 {
+            let pos = random.point(low, high);
+            let max = if random.next() < 0.25 {
+                f64::INFINITY
+            } else {
+                random.range(0.0, 10.0)
+            };
+            assert_nearest(&segments, pos, max);
+        }
+    }
+
+    #[test]
+    fn nearest_far_outside() {
+        let (_, segments) = segments();
+        for &pos in &[
+            vec2(-1000.0, 20.0),
+            vec2(1000.0, 1000.0),
+            vec2(40.0, -500.0),
+            vec2(-300.5, 700.25),
+        ] {
+            assert_nearest(&segments, pos, f64::INFINITY);
+            assert_nearest(&segments, pos, 10.0);
+            assert!(segments.nearest(pos, 10.0).is_none());
+        }
+    }
+
+    /// Whether the segment from `a` to `b` touches the box, by clipping it to the box.
+    fn brute_touches_box(
+        a: Vector2<f64>,
+        b: Vector2<f64>,
+        min: Vector2<f64>,
+        max: Vector2<f64>,
+    ) -> bool {
+        let d = b - a;
+        let (mut enter, mut exit) = (0.0f64, 1.0f64);
+        for axis in 0..2 {
+            if d[axis] == 0.0 {
+                if a[axis] < min[axis] || a[axis] > max[axis] {
+                    return false;
+                }
+                continue;
+            }
+            let t0 = (min[axis] - a[axis]) / d[axis];
+            let t1 = (max[axis] - a[axis]) / d[axis];
+            enter = enter.max(t0.min(t1));
+            exit = exit.min(t0.max(t1));
+        }
+        enter <= exit
+    }
+
+    fn assert_in_box(segments: &Segments, min: Vector2<f64>, max: Vector2<f64>) {
+        let expected = all_segments(segments)
+            .into_iter()
+            .enumerate()
+            .filter(|&(_, (a, b))| brute_touches_box(a, b, min, max))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        assert_eq!(segments.in_box(min, max), expected, "{:?} {:?}", min, max);
+    }
+
+    #[test]
+    fn in_box_matches_brute_force() {
+        let (_, segments) = segments();
+        let (low, high) = surroundings(&segments);
+        let mut random = Random(0x0f1e_2d3c_4b5a_6978);
+        for _ in 0..2000 {
+            let a = random.point(low, high);
+            let size = vec2(random.range(0.0, 15.0), random.range(0.0, 15.0));
+            assert_in_box(&segments, a, a + size);
+        }
+    }
+
+    #[test]
+    fn in_box_edges() {
+        let (_, segments) = segments();
+        let (min, max) = segments.bounds();
+        let everything = segments.in_box(min - vec2(1.0, 1.0), max + vec2(1.0, 1.0));
+        assert_eq!(everything, (0..segments.num_segments()).collect::<Vec<_>>());
+        assert_in_box(&segments, min - vec2(50.0, 50.0), min - vec2(40.0, 40.0));
+        assert_in_box(&segments, max + vec2(40.0, 40.0), max + vec2(50.0, 50.0));
+        assert_in_box(&segments, vec2(min.x - 5.0, 20.0), vec2(min.x + 5.0, 21.0));
+        assert_in_box(&segments, vec2(40.3, 20.7), vec2(40.3, 20.7));
+        assert_in_box(&segments, vec2(30.1, 10.2), vec2(30.1, 10.2));
+    }
+
+    /// Even-odd test over the level polygons themselves.
+    fn brute_in_ground(polygons: &[Polygon], pos: Vector2<f64>) -> bool {
+        let inside = polygons
+            .iter()
+            .filter(|polygon| !polygon.grass)
+            .filter(|polygon| {
+                let mut inside = false;
+                let mut prev = polygon.vertices.last().unwrap();
+                for vertex in &polygon.vertices {
+                    if (vertex.y > pos.y) != (prev.y > pos.y)
+                        && pos.x
+                            < vertex.x
+                                + (prev.x - vertex.x) * (pos.y - vertex.y) / (prev.y - vertex.y)
+                    {
+                        inside = !inside;
+                    }
+                    prev = vertex;
+                }
+                inside
+            })
+            .count();
+        inside % 2 == 0
+    }
+
+    #[test]
+    fn in_ground_matches_brute_force() {
+        let (polygons, segments) = segments();
+        let (low, high) = surroundings(&segments);
+        let mut random = Random(0x7777_1111_3333_5555);
+        for _ in 0..2000 {
+            let pos = random.point(low, high);
+            assert_eq!(
+                segments.in_ground(pos),
+                brute_in_ground(&polygons, pos),
+                "{:?}",
+                pos
+            );
+        }
+    }
+
+    #[test]
+    fn in_ground_nested() {
+        let (_, segments) = segments();
+        // Outside the level, then one to four polygons deep.
+        assert!(segments.in_ground(vec2(-100.0, 20.0)));
+        assert!(!segments.in_ground(vec2(20.0, 20.0)));
+        assert!(segments.in_ground(vec2(32.0, 12.0)));
+        assert!(!segments.in_ground(vec2(36.0, 16.0)));
+        assert!(segments.in_ground(vec2(40.0, 20.0)));
+        // Inside the triangle, and the grass square counts as air.
+        assert!(segments.in_ground(vec2(23.0, 33.0)));
+        assert!(!segments.in_ground(vec2(37.0, 32.0)));
+    }
+}
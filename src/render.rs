@@ -11,6 +11,7 @@ use std::ptr;
 pub struct Renderer {
     polygons: Pass,
     pictures: Pass,
+    hud: Pass,
     texture: GLuint,
 }
 
@@ -84,6 +85,14 @@ impl Viewport {
             size,
         }
     }
+
+    /// Viewport measured in pixels from the bottom left corner of the screen.
+    pub fn screen(screen_size: PhysicalSize<u32>) -> Self {
+        Viewport {
+            position: vec2(0.0, 0.0),
+            size: vec2(screen_size.width as f64, screen_size.height as f64),
+        }
+    }
 }
 
 unsafe fn glsl_version(gl: &Gl) /*-> (u32, u32, u32) */
@@ -210,6 +219,12 @@ impl Renderer {
             include_str!("shader/picture.frag"),
         );
 
+        let hud = Pass::new(
+            gl,
+            include_str!("shader/picture.vert"),
+            include_str!("shader/picture.frag"),
+        );
+
         let mut texture = 0;
         gl.GenTextures(1, &mut texture);
         gl.BindTexture(gl::TEXTURE_2D, texture);
@@ -264,6 +279,7 @@ impl Renderer {
         Renderer {
             polygons,
             pictures,
+            hud,
             texture,
         }
     }
@@ -337,9 +353,33 @@ impl Renderer {
         self.pictures.draw(gl, vertices, indices, viewport);
     }
 
+    pub unsafe fn draw_hud(
+        &mut self,
+        gl: &Gl,
+        vertices: &Vec<PictureVertex>,
+        indices: &Vec<u32>,
+        screen_size: PhysicalSize<u32>,
+    ) {
+        if vertices.is_empty() || indices.is_empty() {
+            return;
+        }
+
+        gl.DepthFunc(gl::ALWAYS);
+        gl.DepthMask(false as _);
+        self.enable(
+            gl,
+            &self.hud,
+            PICTURE_ATTRIBUTES,
+            size_of::<PictureVertex>(),
+        );
+        self.hud
+            .draw(gl, vertices, indices, Viewport::screen(screen_size));
+    }
+
     pub unsafe fn cleanup(&mut self, gl: &Gl) {
         self.polygons.cleanup(gl);
         self.pictures.cleanup(gl);
+        self.hud.cleanup(gl);
         gl.DeleteTextures(1, [self.texture].as_ptr());
     }
 }
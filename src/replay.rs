@@ -0,0 +1,125 @@
+use crate::check::LevelError;
+use crate::game::{GameState, Snapshot, FRAME_TIME};
+use crate::physics::{Control, Events, TIME_STEP};
+use cgmath::{vec2, InnerSpace};
+use elma::lev::Level;
+use elma::rec::{EventType, Ride};
+
+/// Bike position difference considered a divergence.
+pub const DIVERGENCE: f64 = 0.01;
+
+/// Elma replays don't record the brake, so rides are simulated without it. A ride that brakes
+/// diverges because of the missing input, not necessarily because the physics differ.
+pub struct Resimulation {
+    /// Largest distance between simulated and recorded bike positions.
+    pub max_divergence: f64,
+    /// First frame where the distance exceeds `DIVERGENCE`.
+    pub diverged_frame: Option<usize>,
+}
+
+/// Simulate a ride from its recorded throttle, turn and volt inputs and compare bike positions
+/// with the recorded frames. The brake is never applied, see `Resimulation`.
+pub fn resimulate(level: Level, ride: &Ride) -> Result<Resimulation, LevelError> {
+    let mut result = Resimulation {
+        max_divergence: 0.0,
+        diverged_frame: None,
+    };
+
+    simulate(level, ride, &mut (), |i, game_state, _| {
+        let frame = &ride.frames[i];
+        let recorded = vec2(frame.bike.x as f64, frame.bike.y as f64);
+        let divergence = (game_state.moto.bike.position - recorded).magnitude();
+        result.max_divergence = result.max_divergence.max(divergence);
+        if divergence > DIVERGENCE && result.diverged_frame.is_none() {
+            result.diverged_frame = Some(i);
+        }
+    })?;
+
+    Ok(result)
+}
+
+/// A ride simulated ahead of time for viewing, with the game state and throttle at each recorded
+/// frame.
+pub struct Playback {
+    frames: Vec<(Snapshot, bool)>,
+}
+
+impl Playback {
+    pub fn new(level: Level, ride: &Ride) -> Result<Playback, LevelError> {
+        let mut frames = Vec::with_capacity(ride.frames.len());
+        simulate(level, ride, &mut (), |i, game_state, _| {
+            frames.push((game_state.snapshot(), ride.frames[i].throttle()));
+        })?;
+        Ok(Playback { frames })
+    }
+
+    /// Physics time of the last frame.
+    pub fn duration(&self) -> f64 {
+        self.frames.len().saturating_sub(1) as f64 * FRAME_TIME
+    }
+
+    /// Put the frame at physics time `t` into `game_state`. Returns whether the throttle is held.
+    pub fn show(&self, game_state: &mut GameState, t: f64) -> bool {
+        let index = ((t / FRAME_TIME).max(0.0) as usize).min(self.frames.len().saturating_sub(1));
+        match self.frames.get(index) {
+            Some((snapshot, throttle)) => {
+                game_state.moto = snapshot.moto.clone();
+                game_state.taken.copy_from_slice(&snapshot.taken);
+                game_state.outcome = snapshot.outcome;
+                *throttle
+            }
+            None => false,
+        }
+    }
+}
+
+/// Simulate a ride from its recorded inputs, sending physics events to `events` and calling
+/// `frame` with the index and game state of each recorded frame once the game reached its time.
+pub fn simulate<E: Events>(
+    level: Level,
+    ride: &Ride,
+    events: &mut E,
+    mut frame: impl FnMut(usize, &GameState, &mut E),
+) -> Result<(), LevelError> {
+    let mut game_state = GameState::from_level(level)?;
+    let mut recorded = ride.events.iter().peekable();
+
+    for (i, recorded_frame) in ride.frames.iter().enumerate() {
+        let t = i as f64 * FRAME_TIME;
+        let control = Control {
+            throttle: recorded_frame.throttle(),
+            ..Control::default()
+        };
+
+        // Inputs are applied by the step ending at the event time. Events at the same time,
+        // like both volts of an alt volt, are applied together.
+        while let Some(event) = recorded.next_if(|event| event.time <= t) {
+            game_state.advance(control, event.time - TIME_STEP * 1.5, events);
+            let mut input = control;
+            let mut pressed = press(&mut input, &event.event_type);
+            while let Some(next) = recorded.next_if(|next| next.time == event.time) {
+                pressed |= press(&mut input, &next.event_type);
+            }
+            if pressed {
+                let t = game_state.moto.time() + TIME_STEP * 0.5;
+                game_state.advance(input, t, events);
+            }
+        }
+        game_state.advance(control, t, events);
+
+        frame(i, &game_state, events);
+    }
+
+    Ok(())
+}
+
+/// Set the input recorded as `event_type`, returns whether it is one.
+fn press(control: &mut Control, event_type: &EventType) -> bool {
+    match event_type {
+        EventType::Turn => control.turn = true,
+        EventType::VoltRight => control.volt_right = true,
+        EventType::VoltLeft => control.volt_left = true,
+        _ => return false,
+    }
+    true
+}
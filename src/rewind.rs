@@ -0,0 +1,85 @@
+use crate::game::{GameState, Outcome, Snapshot, TIME_SCALE};
+use crate::physics::{Control, Events};
+use std::collections::VecDeque;
+
+/// Physics time between snapshots, 10 ms of real time.
+pub const REWIND_INTERVAL: f64 = 0.01 * TIME_SCALE;
+
+/// Snapshots kept by default, two minutes of real time.
+pub const REWIND_CAPACITY: usize = 12000;
+
+/// Ring buffer of recent game states. Rewinding turns the run into a practice run, which gets no
+/// official time.
+pub struct Rewind {
+    snapshots: VecDeque<Snapshot>,
+    capacity: usize,
+}
+
+impl Rewind {
+    pub fn new(capacity: usize) -> Rewind {
+        Rewind {
+            snapshots: VecDeque::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Advance the game to time `t` like `GameState::advance`, saving a snapshot every
+    /// `REWIND_INTERVAL`.
+    pub fn advance(
+        &mut self,
+        game_state: &mut GameState,
+        control: Control,
+        t: f64,
+        events: &mut impl Events,
+    ) {
+        if self.snapshots.is_empty() {
+            self.record(game_state);
+        }
+
+        let mut control = control;
+        while game_state.outcome == Outcome::Playing {
+            let next = self.snapshots.back().unwrap().moto.time() + REWIND_INTERVAL;
+            if next > t {
+                break;
+            }
+            let time = game_state.moto.time();
+            game_state.advance(control, next, events);
+            if game_state.moto.time() != time {
+                control = control.held();
+            }
+            self.record(game_state);
+        }
+        game_state.advance(control, t, events);
+    }
+
+    fn record(&mut self, game_state: &GameState) {
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(game_state.snapshot());
+    }
+
+    /// Go back to the latest snapshot at or before time `t`, or the oldest one kept, and return
+    /// its time. Later snapshots are dropped, so the run continues from there. The run becomes a
+    /// practice run, see `GameState::restore`.
+    pub fn rewind(&mut self, game_state: &mut GameState, t: f64) -> Option<f64> {
+        while self.snapshots.len() > 1 && self.snapshots.back().unwrap().moto.time() > t {
+            self.snapshots.pop_back();
+        }
+
+        let snapshot = self.snapshots.back()?;
+        game_state.restore(snapshot).unwrap();
+        Some(snapshot.moto.time())
+    }
+
+    /// Drop snapshots after time `t`, after the game jumped back on its own.
+    pub fn truncate(&mut self, t: f64) {
+        while self.snapshots.back().is_some_and(|s| s.moto.time() > t) {
+            self.snapshots.pop_back();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+}
@@ -1,13 +1,14 @@
 use crate::atlas::{Atlas, Sprite};
+use crate::check::LevelError;
+use crate::game::FRAME_TIME;
 use crate::render::{PictureVertex, PolygonVertex, Viewport};
 use crate::transform::Transform;
 use crate::triangulation::triangulate;
-use cgmath::{vec2, Vector2};
+use cgmath::{vec2, InnerSpace, Vector2};
 use elma::constants::OBJECT_RADIUS;
-use elma::lev::{Level, ObjectType};
+use elma::lev::{Level, ObjectType, Picture, Polygon};
 use elma::Clip;
 use lyon_tessellation::VertexBuffers;
-use std::cmp::{max, min};
 
 /*
 1st pass - render polygons with depth
@@ -23,11 +24,16 @@ pub struct Scene {
     ground: usize,
     ground_size: Vector2<f64>,
     objects: Vec<Object>,
+    /// Where in `indices` bike images are inserted.
+    moto_index: usize,
 }
 
 struct Object {
     index: usize,
-    position_y: Option<f64>,
+    /// Index in `Level::objects`.
+    level_index: usize,
+    /// Corners of the image, restored when a taken object comes back.
+    rect: [[f32; 2]; 4],
     bounds: [f32; 4],
     num_frames: i32,
 }
@@ -56,10 +62,12 @@ fn vec_dir(i: i32) -> Vector2<f64> {
 }
 
 const PIXELS_PER_UNIT: f64 = 48.0;
+/// Picture distance of the bike and objects, pictures with smaller distance are drawn in front.
+const BIKE_DISTANCE: i32 = 500;
+const SKY_PARALLAX: f64 = 0.5;
 
 impl Scene {
-    pub fn new(level: &mut Level, atlas: &Atlas) -> Scene {
-        let grass_texture = atlas.get("QGRASS");
+    pub fn new(level: &mut Level, atlas: &Atlas) -> Result<Scene, LevelError> {
         let sky_texture = atlas.get(&level.sky);
         let ground_texture = atlas.get(&level.ground);
         let sky_size = sky_texture.size;
@@ -68,7 +76,7 @@ impl Scene {
         let polygons = triangulate(&level, false, |position| PolygonVertex {
             position,
             clip: 0.0,
-        });
+        })?;
 
         let vertices = Vec::new();
         let indices = Vec::new();
@@ -82,104 +90,172 @@ impl Scene {
             ground: 0,
             ground_size,
             objects: Vec::new(),
+            moto_index: 0,
         };
 
         scene.sky = scene.add_image(sky_texture, vec2(0.0, 0.0), Clip::Sky, false);
         scene.ground = scene.add_image(ground_texture, vec2(0.0, 0.0), Clip::Ground, false);
 
-        let grass = triangulate(&level, true, |position| PictureVertex {
-            position,
-            tex_coord: position,
-            tex_bounds: grass_texture.bounds,
-            mask: [-1.0, -1.0],
-            clip: 0.0,
-        });
-        let num_vertices = scene.vertices.len();
-        scene.vertices.extend_from_slice(&grass.vertices);
-        scene
-            .indices
-            .extend(grass.indices.into_iter().map(|i| i + num_vertices as u32));
+        let grass_pictures = grass_pictures(atlas);
+        for polygon in &level.polygons {
+            // Grass is laid along its vertices, broken ones would leave nothing to lay it on.
+            let finite = polygon
+                .vertices
+                .iter()
+                .all(|v| v.x.is_finite() && v.y.is_finite());
+            if polygon.grass && polygon.vertices.len() >= 3 && finite {
+                scene.add_grass(&grass_pictures, polygon);
+            }
+        }
 
+        // Pictures further away than the bike are drawn first, followed by objects, the bike and
+        // the rest of the pictures.
         level.pictures.sort_by_key(|picture| picture.distance);
         for pic in level.pictures.iter().rev() {
-            if !pic.name.is_empty() {
-                let sprite = atlas.get(&pic.name);
-                scene.add_image(
-                    sprite,
-                    vec2(pic.position.x, pic.position.y),
-                    pic.clip,
-                    false,
-                );
-            } else if !pic.texture.is_empty() && !pic.mask.is_empty() {
-                let texture = atlas.get(&pic.texture);
-                let mask = atlas.get(&pic.mask);
-
-                let mask_pos = vec2(mask.bounds[0] as f64, mask.bounds[1] as f64);
-                let mask_size = vec2(mask.bounds[2] as f64, mask.bounds[3] as f64) - mask_pos;
-
-                let v = scene.vertices.len() as u32;
-                for i in 0..4 {
-                    let v = vec_dir(i);
-                    let p = vec2(pic.position.x, pic.position.y)
-                        + (1.0 / PIXELS_PER_UNIT) * vec2(v.x * mask.size.x, -v.y * mask.size.y);
-                    let mask = mask_pos + vec2(v.x * mask_size.x, v.y * mask_size.y);
-
-                    scene.vertices.push(PictureVertex {
-                        position: [p.x as f32, p.y as f32],
-                        tex_coord: position_to_tex_coord(p, texture.size),
-                        tex_bounds: texture.bounds,
-                        mask: [mask.x as f32, mask.y as f32],
-                        clip: match pic.clip {
-                            Clip::Unclipped => 0.5,
-                            Clip::Ground => 0.0,
-                            Clip::Sky => 1.0,
-                        },
-                    });
-                }
-
-                scene
-                    .indices
-                    .extend_from_slice(&[v, v + 1, v + 2, v, v + 2, v + 3]);
+            if pic.distance >= BIKE_DISTANCE {
+                scene.add_picture(atlas, pic);
             }
         }
 
-        for object in &level.objects {
-            let name;
-            let name = match object.object_type {
-                ObjectType::Apple { animation, .. } => {
-                    name = format!("qfood{}", min(1, max(2, animation)));
-                    &name
-                }
-                ObjectType::Exit => "QEXIT",
-                ObjectType::Killer => "QKILLER",
+        for (level_index, object) in level.objects.iter().enumerate() {
+            let sprite = match object.object_type {
+                // Levels may refer to animations missing in the LGR.
+                ObjectType::Apple { animation, .. } => atlas
+                    .sprites
+                    .get(&format!("qfood{}", animation.clamp(1, 9)))
+                    .unwrap_or_else(|| atlas.get("qfood1")),
+                ObjectType::Exit => atlas.get("QEXIT"),
+                ObjectType::Killer => atlas.get("QKILLER"),
                 ObjectType::Player => continue,
             };
 
-            let sprite = atlas.get(name);
             let index = scene.add_image(
                 sprite,
                 vec2(object.position.x, object.position.y) - vec2(OBJECT_RADIUS, -OBJECT_RADIUS),
                 Clip::Unclipped,
                 true,
             );
+            let mut rect = [[0.0; 2]; 4];
+            for (corner, vertex) in rect.iter_mut().zip(&scene.vertices[index..index + 4]) {
+                *corner = vertex.position;
+            }
             scene.objects.push(Object {
                 index,
+                level_index,
+                rect,
                 bounds: sprite.bounds,
-                position_y: (object.object_type != ObjectType::Killer).then_some(object.position.y),
                 num_frames: (sprite.size.x / sprite.size.y).round() as i32,
             });
         }
 
-        scene
+        scene.moto_index = scene.indices.len();
+        for pic in level.pictures.iter().rev() {
+            if pic.distance < BIKE_DISTANCE {
+                scene.add_picture(atlas, pic);
+            }
+        }
+
+        Ok(scene)
     }
 
+    /// Place grass pictures from left to right along the polygon edges, leaving out the longest
+    /// edge, picking at each step the picture which stays closest to the edges.
+    fn add_grass(&mut self, pictures: &[GrassPicture], polygon: &Polygon) {
+        if pictures.is_empty() {
+            return;
+        }
+
+        let vertices: Vec<_> = polygon
+            .vertices
+            .iter()
+            .map(|v| PIXELS_PER_UNIT * vec2(v.x, v.y))
+            .collect();
+        let n = vertices.len();
+        let edge_length = |i: usize| (vertices[(i + 1) % n] - vertices[i]).magnitude();
+        let longest = (0..n)
+            .max_by(|&i, &j| edge_length(i).total_cmp(&edge_length(j)))
+            .unwrap();
+
+        let mut chain: Vec<_> = (1..=n).map(|i| vertices[(longest + i) % n]).collect();
+        if chain[0].x > chain[n - 1].x {
+            chain.reverse();
+        }
+
+        let mut x = chain[0].x;
+        let mut y = chain[0].y;
+        while x < chain[n - 1].x {
+            let picture = pictures
+                .iter()
+                .min_by(|a, b| {
+                    let error_a = (y + a.dy - chain_y(&chain, x + a.sprite.size.x)).abs();
+                    let error_b = (y + b.dy - chain_y(&chain, x + b.sprite.size.x)).abs();
+                    error_a.total_cmp(&error_b)
+                })
+                .unwrap();
+
+            let top = y.max(y + picture.dy);
+            self.add_image(
+                &picture.sprite,
+                vec2(x, top) / PIXELS_PER_UNIT,
+                Clip::Ground,
+                false,
+            );
+
+            x += picture.sprite.size.x;
+            y += picture.dy;
+        }
+    }
+
+    fn add_picture(&mut self, atlas: &Atlas, pic: &Picture) {
+        if !pic.name.is_empty() {
+            let sprite = atlas.get(&pic.name);
+            self.add_image(
+                sprite,
+                vec2(pic.position.x, pic.position.y),
+                pic.clip,
+                false,
+            );
+        } else if !pic.texture.is_empty() && !pic.mask.is_empty() {
+            let texture = atlas.get(&pic.texture);
+            let mask = atlas.get(&pic.mask);
+
+            let mask_pos = vec2(mask.bounds[0] as f64, mask.bounds[1] as f64);
+            let mask_size = vec2(mask.bounds[2] as f64, mask.bounds[3] as f64) - mask_pos;
+
+            let v = self.vertices.len() as u32;
+            for i in 0..4 {
+                let v = vec_dir(i);
+                let p = vec2(pic.position.x, pic.position.y)
+                    + (1.0 / PIXELS_PER_UNIT) * vec2(v.x * mask.size.x, -v.y * mask.size.y);
+                let mask = mask_pos + vec2(v.x * mask_size.x, v.y * mask_size.y);
+
+                self.vertices.push(PictureVertex {
+                    position: [p.x as f32, p.y as f32],
+                    tex_coord: position_to_tex_coord(p, texture.size),
+                    tex_bounds: texture.bounds,
+                    mask: [mask.x as f32, mask.y as f32],
+                    clip: match pic.clip {
+                        Clip::Unclipped => 0.5,
+                        Clip::Ground => 0.0,
+                        Clip::Sky => 1.0,
+                    },
+                });
+            }
+
+            self.indices
+                .extend_from_slice(&[v, v + 1, v + 2, v, v + 2, v + 3]);
+        }
+    }
+
+    /// Add bike images, drawn between pictures in front of and behind the bike.
     pub fn add_moto(&mut self, atlas: &Atlas, i: bool) -> Moto {
+        let num_indices = self.indices.len();
         let mut add = |name| {
             let sprite = atlas.get(&format!("Q{}{}", i as u8 + 1, name));
             self.add_image(sprite, vec2(0.0, 0.0), Clip::Unclipped, false)
         };
 
-        Moto {
+        let moto = Moto {
             wheels: [add("WHEEL"), add("WHEEL")],
             suspension1: add("SUSP1"),
             suspension2: add("SUSP2"),
@@ -190,7 +266,15 @@ impl Scene {
             leg: add("LEG"),
             upper_arm: add("UP_ARM"),
             forearm: add("FORARM"),
-        }
+        };
+
+        let indices: Vec<u32> = self.indices.drain(num_indices..).collect();
+        let moto_index = self.moto_index;
+        self.indices
+            .splice(moto_index..moto_index, indices.iter().cloned());
+        self.moto_index += indices.len();
+
+        moto
     }
 
     pub fn add_image(
@@ -230,23 +314,24 @@ impl Scene {
         v as usize
     }
 
-    pub fn animate(&mut self, time: f64) {
-        let frame = (time * 30.0) as i32; // FIXME: exact framerate is unknown
+    /// Advance object animations to physics time `time`. Objects are animated at the replay frame
+    /// rate and, like in the original game, stay at a fixed position.
+    pub fn animate(&mut self, time: f64, taken: &[bool]) {
+        let frame = (time / FRAME_TIME) as i32;
         for object in &self.objects {
             let vertices = &mut self.vertices[object.index..object.index + 4];
             let frame = (frame % object.num_frames) as f32;
 
-            if let Some(position_y) = object.position_y {
-                let shift = (time * 4.0).sin() * 0.1; // FIXME: exact parameters are unknown
-                let top = (position_y + OBJECT_RADIUS + shift) as f32;
-                let bottom = (position_y - OBJECT_RADIUS + shift) as f32;
-                vertices[0].position[1] = top;
-                vertices[1].position[1] = top;
-                vertices[2].position[1] = bottom;
-                vertices[3].position[1] = bottom;
+            // Taken objects collapse to a point, they can come back on restart, rewind or respawn.
+            if taken[object.level_index] {
+                for vertex in vertices {
+                    vertex.position = object.rect[0];
+                }
+                continue;
             }
 
-            for vertex in vertices {
+            for (vertex, &corner) in vertices.iter_mut().zip(&object.rect) {
+                vertex.position = corner;
                 vertex.tex_bounds = [
                     object.bounds[0] + (object.bounds[3] - object.bounds[1]) * frame,
                     object.bounds[1],
@@ -258,40 +343,16 @@ impl Scene {
     }
 
     pub fn update(&mut self, viewport: Viewport) {
-        let sky = &mut self.vertices[self.sky..];
-        //  let sky_width =
-        //      sky_size.y as f64 / sky_size.x as f64 * viewport.size.x / viewport.size.y;
-        //   let sky_offset = viewport.position.x;
+        let sky = sky_tex_coords(viewport, self.sky_size);
+        let ground = ground_tex_coords(viewport, self.ground_size);
         for i in 0..4 {
-            /*    let v = vec_dir(i);
-            let p = viewport.position + vec2(viewport.size.x * v.x, viewport.size.y * v.y);
-            sky[i as usize].position = [p.x as f32, p.y as f32];
-            if v.x > 0.5 {
-                sky[i as usize].tex_coord[0] = sky_width as f32;
-            } else {
-                sky[i as usize].tex_coord[0] = 0.0;
-            }
-
-            let tex_coord_a = PIXELS_PER_UNIT * viewport.position.x / sky_size.x;
-            //    let tex_coord = PIXELS_PER_UNIT * viewport.size.x / sky_size.x;
-            let tex_coord = 0.5 * tex_coord_a; // + tex_coord * v.x;
-            sky[i as usize].tex_coord[0] += tex_coord as f32;*/
-
-            let v = vec_dir(i);
+            let v = vec_dir(i as i32);
             let p = viewport.position + vec2(viewport.size.x * v.x, viewport.size.y * v.y);
-            sky[i as usize].position = [p.x as f32, p.y as f32];
-
-            let p = 0.5 * vec2(viewport.position.x, 0.0)
-                + vec2(viewport.size.x * v.x, viewport.size.y * v.y);
-            sky[i as usize].tex_coord = position_to_tex_coord(p, self.sky_size);
-        }
 
-        let ground = &mut self.vertices[self.ground..];
-        for i in 0..4 {
-            let v = vec_dir(i);
-            let p = viewport.position + vec2(viewport.size.x * v.x, viewport.size.y * v.y);
-            ground[i as usize].position = [p.x as f32, p.y as f32];
-            ground[i as usize].tex_coord = position_to_tex_coord(p, self.ground_size);
+            self.vertices[self.sky + i].position = [p.x as f32, p.y as f32];
+            self.vertices[self.sky + i].tex_coord = sky[i];
+            self.vertices[self.ground + i].position = [p.x as f32, p.y as f32];
+            self.vertices[self.ground + i].tex_coord = ground[i];
         }
     }
 
@@ -332,7 +393,294 @@ impl Scene {
     }
 }
 
+struct GrassPicture {
+    sprite: Sprite,
+    /// Rise of the grass edge from left to right in pixels.
+    dy: f64,
+}
+
+/// Collect `QUP_<n>` and `QDOWN_<n>` pictures.
+fn grass_pictures(atlas: &Atlas) -> Vec<GrassPicture> {
+    let mut pictures = Vec::new();
+    for (name, sprite) in &atlas.sprites {
+        let name = name.to_ascii_uppercase();
+        let dy = if let Some(n) = name.strip_prefix("QUP_") {
+            n.parse::<f64>().ok()
+        } else if let Some(n) = name.strip_prefix("QDOWN_") {
+            n.parse::<f64>().ok().map(|n| -n)
+        } else {
+            None
+        };
+
+        if let Some(dy) = dy {
+            if sprite.size.x >= 1.0 {
+                pictures.push(GrassPicture {
+                    sprite: *sprite,
+                    dy,
+                });
+            }
+        }
+    }
+    pictures
+}
+
+/// Height of the x-ordered polyline at `x`.
+fn chain_y(chain: &[Vector2<f64>], x: f64) -> f64 {
+    if x <= chain[0].x {
+        return chain[0].y;
+    }
+
+    for segment in chain.windows(2) {
+        let (a, b) = (segment[0], segment[1]);
+        if (a.x <= x && x <= b.x) || (b.x <= x && x <= a.x) {
+            if a.x == b.x {
+                return a.y.max(b.y);
+            }
+            return a.y + (b.y - a.y) * (x - a.x) / (b.x - a.x);
+        }
+    }
+
+    chain[chain.len() - 1].y
+}
+
+/// Sky scrolls horizontally at half the camera speed and stays attached to the top of the
+/// screen vertically, one texel per level pixel.
+fn sky_tex_coords(viewport: Viewport, size: Vector2<f64>) -> [[f32; 2]; 4] {
+    let mut tex_coords = [[0.0; 2]; 4];
+    for i in 0..4 {
+        let v = vec_dir(i);
+        let p = vec2(
+            SKY_PARALLAX * viewport.position.x + viewport.size.x * v.x,
+            viewport.size.y * (v.y - 1.0),
+        );
+        tex_coords[i as usize] = position_to_tex_coord(p, size);
+    }
+    tex_coords
+}
+
+/// Ground is tiled in level space starting at the origin.
+fn ground_tex_coords(viewport: Viewport, size: Vector2<f64>) -> [[f32; 2]; 4] {
+    let mut tex_coords = [[0.0; 2]; 4];
+    for i in 0..4 {
+        let v = vec_dir(i);
+        let p = viewport.position + vec2(viewport.size.x * v.x, viewport.size.y * v.y);
+        tex_coords[i as usize] = position_to_tex_coord(p, size);
+    }
+    tex_coords
+}
+
 fn position_to_tex_coord(position: Vector2<f64>, size: Vector2<f64>) -> [f32; 2] {
     let tex_coord = PIXELS_PER_UNIT * vec2(position.x / size.x, -position.y / size.y);
     [tex_coord.x as f32, tex_coord.y as f32]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glutin::dpi::PhysicalSize;
+    use std::collections::BTreeMap;
+
+    const SKY_SIZE: Vector2<f64> = vec2(640.0, 480.0);
+    const GROUND_SIZE: Vector2<f64> = vec2(256.0, 256.0);
+
+    fn assert_tex_coords(actual: [[f32; 2]; 4], expected: [[f32; 2]; 4]) {
+        for (a, e) in actual.iter().zip(&expected) {
+            assert!(
+                (a[0] - e[0]).abs() < 1e-5 && (a[1] - e[1]).abs() < 1e-5,
+                "{:?} != {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    fn viewport(center: Vector2<f64>, scale: f64, width: u32, height: u32) -> Viewport {
+        Viewport::from_center_and_scale(center, scale, PhysicalSize::new(width, height))
+    }
+
+    #[test]
+    fn explicit_viewport() {
+        let viewport = Viewport {
+            position: vec2(10.0, -2.0),
+            size: vec2(20.0, 15.0),
+        };
+        assert_tex_coords(
+            sky_tex_coords(viewport, SKY_SIZE),
+            [[0.375, 1.5], [1.875, 1.5], [1.875, 0.0], [0.375, 0.0]],
+        );
+        assert_tex_coords(
+            ground_tex_coords(viewport, GROUND_SIZE),
+            [
+                [1.875, 0.375],
+                [5.625, 0.375],
+                [5.625, -2.4375],
+                [1.875, -2.4375],
+            ],
+        );
+    }
+
+    #[test]
+    fn landscape_at_default_zoom() {
+        let viewport = viewport(vec2(0.0, 0.0), 10.0, 1024, 768);
+        assert_tex_coords(
+            sky_tex_coords(viewport, SKY_SIZE),
+            [
+                [-0.216506, 0.866025],
+                [0.649519, 0.866025],
+                [0.649519, 0.0],
+                [-0.216506, 0.0],
+            ],
+        );
+        assert_tex_coords(
+            ground_tex_coords(viewport, GROUND_SIZE),
+            [
+                [-1.082532, 0.811899],
+                [1.082532, 0.811899],
+                [1.082532, -0.811899],
+                [-1.082532, -0.811899],
+            ],
+        );
+    }
+
+    #[test]
+    fn widescreen_zoomed_in() {
+        let viewport = viewport(vec2(35.5, -12.25), 5.0, 1920, 1080);
+        assert_tex_coords(
+            sky_tex_coords(viewport, SKY_SIZE),
+            [
+                [1.20625, 0.375],
+                [1.70625, 0.375],
+                [1.70625, 0.0],
+                [1.20625, 0.0],
+            ],
+        );
+        assert_tex_coords(
+            ground_tex_coords(viewport, GROUND_SIZE),
+            [
+                [6.03125, 2.648438],
+                [7.28125, 2.648438],
+                [7.28125, 1.945312],
+                [6.03125, 1.945312],
+            ],
+        );
+    }
+
+    #[test]
+    fn portrait_zoomed_out() {
+        let viewport = viewport(vec2(-100.0, 40.0), 40.0, 768, 1024);
+        assert_tex_coords(
+            sky_tex_coords(viewport, SKY_SIZE),
+            [
+                [-4.399519, 4.618802],
+                [-1.801443, 4.618802],
+                [-1.801443, 0.0],
+                [-4.399519, 0.0],
+            ],
+        );
+        assert_tex_coords(
+            ground_tex_coords(viewport, GROUND_SIZE),
+            [
+                [-21.997595, -3.169873],
+                [-15.502405, -3.169873],
+                [-15.502405, -11.830127],
+                [-21.997595, -11.830127],
+            ],
+        );
+    }
+
+    #[test]
+    fn sky_ignores_vertical_camera_movement() {
+        let low = viewport(vec2(3.0, -50.0), 10.0, 1024, 768);
+        let high = viewport(vec2(3.0, 50.0), 10.0, 1024, 768);
+        assert_eq!(
+            sky_tex_coords(low, SKY_SIZE),
+            sky_tex_coords(high, SKY_SIZE)
+        );
+    }
+
+    fn atlas() -> Atlas {
+        let sprite = |width: f64, height: f64| Sprite {
+            bounds: [0.0, 0.0, 1.0, 1.0],
+            size: vec2(width, height),
+        };
+        let mut sprites = BTreeMap::new();
+        sprites.insert("sky".to_string(), sprite(640.0, 480.0));
+        sprites.insert("ground".to_string(), sprite(256.0, 256.0));
+        sprites.insert("qfood1".to_string(), sprite(400.0, 40.0));
+        sprites.insert("QEXIT".to_string(), sprite(400.0, 40.0));
+        sprites.insert("QKILLER".to_string(), sprite(400.0, 40.0));
+        sprites.insert("QUP_14".to_string(), sprite(20.0, 40.0));
+        sprites.insert("QDOWN_14".to_string(), sprite(20.0, 40.0));
+        Atlas {
+            sprites,
+            glyphs: BTreeMap::new(),
+            data: Vec::new(),
+            width: 1,
+            height: 1,
+        }
+    }
+
+    #[test]
+    fn taken_object_comes_back() {
+        let mut level = Level::new();
+        level.objects.push(elma::lev::Object {
+            position: elma::Position::new(5.0, 3.0),
+            object_type: ObjectType::Apple {
+                gravity: elma::lev::GravityDirection::None,
+                animation: 1,
+            },
+        });
+        let apple = level.objects.len() - 1;
+        let mut scene = Scene::new(&mut level, &atlas()).unwrap();
+        let positions = |scene: &Scene| {
+            let object = scene
+                .objects
+                .iter()
+                .find(|o| o.level_index == apple)
+                .unwrap();
+            scene.vertices[object.index..object.index + 4]
+                .iter()
+                .map(|vertex| vertex.position)
+                .collect::<Vec<_>>()
+        };
+
+        let mut taken = vec![false; level.objects.len()];
+        scene.animate(0.0, &taken);
+        let shown = positions(&scene);
+        assert!(shown[0] != shown[2]);
+
+        taken[apple] = true;
+        scene.animate(1.0, &taken);
+        assert!(positions(&scene).iter().all(|&p| p == shown[0]));
+
+        taken[apple] = false;
+        scene.animate(2.0, &taken);
+        assert_eq!(positions(&scene), shown);
+    }
+
+    #[test]
+    fn broken_grass_is_skipped() {
+        let grass = |vertices: &[(f64, f64)]| Polygon {
+            grass: true,
+            vertices: vertices
+                .iter()
+                .map(|&(x, y)| elma::Position::new(x, y))
+                .collect(),
+        };
+        let square = [(0.0, 0.0), (10.0, 0.0), (10.0, 1.0), (0.0, 1.0)];
+        let vertex_count = |polygons: Vec<Polygon>| {
+            let mut level = Level::new();
+            level.polygons.extend(polygons);
+            Scene::new(&mut level, &atlas()).unwrap().vertices.len()
+        };
+
+        let without = vertex_count(Vec::new());
+        assert!(vertex_count(vec![grass(&square)]) > without);
+
+        let mut nan = square;
+        nan[1].0 = f64::NAN;
+        let mut infinite = square;
+        infinite[2].0 = f64::INFINITY;
+        assert_eq!(vertex_count(vec![grass(&nan), grass(&infinite)]), without);
+    }
+}
@@ -0,0 +1,143 @@
+//! Binary save states.
+//!
+//! Layout, all numbers little endian: magic `E2SS`, `u32` version, then the bike (`f64`s, `u8`
+//! booleans and the `u8` volt direction in the order of `Moto::write`), `u32` object count
+//! followed by one `u8` per object for taken objects, and a `u8` outcome.
+
+use crate::game::{Outcome, Snapshot};
+use crate::physics::Moto;
+use std::fmt;
+
+const MAGIC: &[u8; 4] = b"E2SS";
+pub const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SnapshotError {
+    Truncated,
+    BadMagic,
+    Version(u32),
+    BadValue,
+    /// Snapshot was saved on a level with a different number of objects.
+    ObjectCount(usize),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Truncated => write!(f, "snapshot is truncated"),
+            SnapshotError::BadMagic => write!(f, "not a snapshot"),
+            SnapshotError::Version(version) => {
+                write!(f, "unsupported snapshot version {}", version)
+            }
+            SnapshotError::BadValue => write!(f, "invalid value in snapshot"),
+            SnapshotError::ObjectCount(count) => {
+                write!(
+                    f,
+                    "snapshot has {} objects, level has a different count",
+                    count
+                )
+            }
+        }
+    }
+}
+
+pub(crate) struct Writer(pub Vec<u8>);
+
+impl Writer {
+    pub fn f64(&mut self, value: f64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.0.push(value as u8);
+    }
+}
+
+pub(crate) struct Reader<'a>(pub &'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], SnapshotError> {
+        if self.0.len() < N {
+            return Err(SnapshotError::Truncated);
+        }
+        let (bytes, rest) = self.0.split_at(N);
+        self.0 = rest;
+        let mut result = [0; N];
+        result.copy_from_slice(bytes);
+        Ok(result)
+    }
+
+    pub fn f64(&mut self) -> Result<f64, SnapshotError> {
+        Ok(f64::from_le_bytes(self.bytes()?))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, SnapshotError> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    pub fn u8(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, SnapshotError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(SnapshotError::BadValue),
+        }
+    }
+}
+
+impl Snapshot {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer(MAGIC.to_vec());
+        writer.u32(VERSION);
+        self.moto.write(&mut writer);
+        writer.u32(self.taken.len() as u32);
+        for &taken in &self.taken {
+            writer.bool(taken);
+        }
+        writer.0.push(match self.outcome {
+            Outcome::Playing => 0,
+            Outcome::Dead => 1,
+            Outcome::Finished => 2,
+        });
+        writer.0
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Snapshot, SnapshotError> {
+        let mut reader = Reader(bytes);
+        if &reader.bytes::<4>()? != MAGIC {
+            return Err(SnapshotError::BadMagic);
+        }
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(SnapshotError::Version(version));
+        }
+
+        let moto = Moto::read(&mut reader)?;
+        let count = reader.u32()? as usize;
+        if count > reader.0.len() {
+            return Err(SnapshotError::Truncated);
+        }
+        let taken = (0..count)
+            .map(|_| reader.bool())
+            .collect::<Result<_, _>>()?;
+        let outcome = match reader.u8()? {
+            0 => Outcome::Playing,
+            1 => Outcome::Dead,
+            2 => Outcome::Finished,
+            _ => return Err(SnapshotError::BadValue),
+        };
+
+        Ok(Snapshot {
+            moto,
+            taken,
+            outcome,
+        })
+    }
+}
@@ -0,0 +1,147 @@
+use crate::check::LevelError;
+use crate::physics::Segments;
+use elma::lev::{GravityDirection, Level, ObjectType};
+use elma::Clip;
+use serde::Serialize;
+use std::fmt;
+
+#[derive(Serialize)]
+pub struct LevelStats {
+    pub name: String,
+    pub link: u32,
+    pub lgr: String,
+    /// Bounding box of ground polygons, `[min_x, min_y, max_x, max_y]`.
+    pub bounds: [f64; 4],
+    pub polygons: usize,
+    pub grass_polygons: usize,
+    pub vertices: usize,
+    pub segments: usize,
+    pub apples: AppleStats,
+    pub killers: usize,
+    pub exits: usize,
+    pub pictures: PictureStats,
+    pub cells: usize,
+    pub max_segments_per_cell: usize,
+}
+
+/// Apples by gravity change.
+#[derive(Serialize, Default)]
+pub struct AppleStats {
+    pub none: usize,
+    pub up: usize,
+    pub down: usize,
+    pub left: usize,
+    pub right: usize,
+}
+
+/// Pictures by clipping.
+#[derive(Serialize, Default)]
+pub struct PictureStats {
+    pub unclipped: usize,
+    pub ground: usize,
+    pub sky: usize,
+}
+
+impl LevelStats {
+    pub fn new(level: &Level) -> Result<LevelStats, LevelError> {
+        let segments = Segments::new(&level.polygons)?;
+        let (min, max) = segments.bounds();
+
+        let mut apples = AppleStats::default();
+        let mut killers = 0;
+        let mut exits = 0;
+        for object in &level.objects {
+            match object.object_type {
+                ObjectType::Apple { gravity, .. } => match gravity {
+                    GravityDirection::None => apples.none += 1,
+                    GravityDirection::Up => apples.up += 1,
+                    GravityDirection::Down => apples.down += 1,
+                    GravityDirection::Left => apples.left += 1,
+                    GravityDirection::Right => apples.right += 1,
+                },
+                ObjectType::Killer => killers += 1,
+                ObjectType::Exit => exits += 1,
+                ObjectType::Player => {}
+            }
+        }
+
+        let mut pictures = PictureStats::default();
+        for picture in &level.pictures {
+            match picture.clip {
+                Clip::Unclipped => pictures.unclipped += 1,
+                Clip::Ground => pictures.ground += 1,
+                Clip::Sky => pictures.sky += 1,
+            }
+        }
+
+        Ok(LevelStats {
+            name: level.title.clone(),
+            link: level.link,
+            lgr: level.lgr.clone(),
+            bounds: [min.x, min.y, max.x, max.y],
+            polygons: level.polygons.len(),
+            grass_polygons: level.polygons.iter().filter(|p| p.grass).count(),
+            vertices: level.polygons.iter().map(|p| p.vertices.len()).sum(),
+            segments: segments.num_segments(),
+            apples,
+            killers,
+            exits,
+            pictures,
+            cells: segments.num_cells(),
+            max_segments_per_cell: segments.max_cell_len(),
+        })
+    }
+}
+
+impl fmt::Display for LevelStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "name: {}", self.name)?;
+        writeln!(f, "link: {}", self.link)?;
+        writeln!(f, "lgr: {}", self.lgr)?;
+        writeln!(
+            f,
+            "bounds: ({}, {}) - ({}, {})",
+            self.bounds[0], self.bounds[1], self.bounds[2], self.bounds[3]
+        )?;
+        writeln!(
+            f,
+            "polygons: {} ({} grass)",
+            self.polygons, self.grass_polygons
+        )?;
+        writeln!(f, "vertices: {}", self.vertices)?;
+        writeln!(f, "segments: {}", self.segments)?;
+        writeln!(
+            f,
+            "apples: {} normal, {} up, {} down, {} left, {} right",
+            self.apples.none, self.apples.up, self.apples.down, self.apples.left, self.apples.right
+        )?;
+        writeln!(f, "killers: {}", self.killers)?;
+        writeln!(f, "exits: {}", self.exits)?;
+        writeln!(
+            f,
+            "pictures: {} unclipped, {} ground, {} sky",
+            self.pictures.unclipped, self.pictures.ground, self.pictures.sky
+        )?;
+        writeln!(f, "cells: {}", self.cells)?;
+        write!(f, "max segments per cell: {}", self.max_segments_per_cell)
+    }
+}
+
+/// `stats <level> [--json]`
+pub fn run(args: &[String]) -> Result<(), String> {
+    let json = args.iter().any(|arg| arg == "--json");
+    let path = args
+        .iter()
+        .find(|arg| !arg.starts_with("--"))
+        .ok_or("usage: stats <level> [--json]")?;
+
+    let level = Level::load(path).map_err(|e| format!("{}: {:?}", path, e))?;
+    let stats = LevelStats::new(&level).map_err(|e| format!("{}: {}", path, e))?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&stats).unwrap());
+    } else {
+        println!("{}", stats);
+    }
+
+    Ok(())
+}
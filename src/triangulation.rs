@@ -1,42 +1,86 @@
-use elma::lev::Level;
+use crate::check::LevelError;
+use elma::lev::{Level, Polygon};
 use lyon_tessellation::geom::math::{point, Point};
 use lyon_tessellation::geometry_builder::{BuffersBuilder, VertexBuffers};
 use lyon_tessellation::path::Path;
-use lyon_tessellation::{FillAttributes, FillOptions, FillTessellator};
+use lyon_tessellation::{FillAttributes, FillOptions, FillRule, FillTessellator};
 
+/// Triangulate ground (or grass) polygons.
+///
+/// Area inside an odd number of polygons is filled, which makes nested polygons alternate
+/// between ground and air like in the original game.
 pub fn triangulate<V>(
     level: &Level,
     grass: bool,
     f: impl Fn([f32; 2]) -> V,
-) -> VertexBuffers<V, u32> {
-    let mut path_builder = Path::builder();
-    for polygon in &level.polygons {
-        if polygon.grass == grass {
-            path_builder.move_to(point(
-                polygon.vertices[0].x as f32,
-                polygon.vertices[0].y as f32,
-            ));
-
-            for p in &polygon.vertices[1..] {
-                path_builder.line_to(point(p.x as f32, p.y as f32));
-            }
+) -> Result<VertexBuffers<V, u32>, LevelError> {
+    let mut polygons = Vec::new();
+    for (i, polygon) in level.polygons.iter().enumerate() {
+        if polygon.grass != grass {
+            continue;
+        }
+
+        if polygon.vertices.len() < 3 {
+            return Err(LevelError::TooFewVertices { polygon: i });
         }
+        if polygon
+            .vertices
+            .iter()
+            .any(|v| !(v.x as f32).is_finite() || !(v.y as f32).is_finite())
+        {
+            return Err(LevelError::Triangulation { polygon: Some(i) });
+        }
+
+        polygons.push(polygon);
     }
-    path_builder.close();
-    let path = path_builder.build();
 
-    // Create the destination vertex and index buffers.
     let mut buffers: VertexBuffers<V, u32> = VertexBuffers::new();
+    if tessellate(&polygons, &mut buffers, &f) {
+        return Ok(buffers);
+    }
+
+    // Find the polygon which can not be triangulated on its own.
+    let polygon = level
+        .polygons
+        .iter()
+        .enumerate()
+        .filter(|(_, polygon)| polygon.grass == grass)
+        .find(|(_, polygon)| !tessellate(&[polygon], &mut VertexBuffers::new(), &f))
+        .map(|(i, _)| i);
+
+    Err(LevelError::Triangulation { polygon })
+}
+
+fn tessellate<V>(
+    polygons: &[&Polygon],
+    buffers: &mut VertexBuffers<V, u32>,
+    f: &impl Fn([f32; 2]) -> V,
+) -> bool {
+    let mut path_builder = Path::builder();
+    for polygon in polygons {
+        path_builder.move_to(point(
+            polygon.vertices[0].x as f32,
+            polygon.vertices[0].y as f32,
+        ));
+
+        for p in &polygon.vertices[1..] {
+            path_builder.line_to(point(p.x as f32, p.y as f32));
+        }
+
+        path_builder.close();
+    }
+    let path = path_builder.build();
 
-    // Create the destination vertex and index buffers.
     let mut vertex_builder =
-        BuffersBuilder::new(&mut buffers, |input: Point, _attributes: FillAttributes| {
+        BuffersBuilder::new(buffers, |input: Point, _attributes: FillAttributes| {
             f([input.x, input.y])
         });
 
-    let result =
-        FillTessellator::new().tessellate_path(&path, &FillOptions::default(), &mut vertex_builder);
-    assert!(result.is_ok());
-
-    buffers
+    FillTessellator::new()
+        .tessellate_path(
+            &path,
+            &FillOptions::default().with_fill_rule(FillRule::EvenOdd),
+            &mut vertex_builder,
+        )
+        .is_ok()
 }
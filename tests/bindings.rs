@@ -0,0 +1,90 @@
+use elma2::bindings::{Action, Bindings};
+use glutin::event::VirtualKeyCode::{self, *};
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+/// Load bindings from `text` written to a file of its own.
+fn load(name: &str, text: &str) -> Result<Bindings, String> {
+    let path: PathBuf =
+        std::env::temp_dir().join(format!("elma2-{}-{}.toml", name, std::process::id()));
+    fs::write(&path, text).unwrap();
+    let bindings = Bindings::load(&path);
+    fs::remove_file(&path).unwrap();
+    bindings
+}
+
+fn pressed(keys: &[VirtualKeyCode]) -> HashSet<VirtualKeyCode> {
+    keys.iter().copied().collect()
+}
+
+#[test]
+fn several_keys_per_action() {
+    let bindings = load(
+        "several",
+        r#"
+throttle = ["Up", "W"]
+brake = ["Down", "S"]
+turn = ["Space", "T"]
+replay_pause = ["P", "Pause"]
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(bindings.throttle, vec![Up, W]);
+    assert!(bindings.control(&pressed(&[W])).throttle);
+    assert!(bindings.control(&pressed(&[Up])).throttle);
+    assert!(bindings.control(&pressed(&[S])).brake);
+    assert!(!bindings.control(&pressed(&[T])).throttle);
+    assert_eq!(bindings.action(Space), Some(Action::Turn));
+    assert_eq!(bindings.action(T), Some(Action::Turn));
+    assert_eq!(bindings.action(Pause), Some(Action::ReplayPause));
+    assert_eq!(bindings.action(W), None);
+}
+
+#[test]
+fn missing_fields_are_defaults() {
+    let bindings = load("missing", "turn = [\"T\"]\n").unwrap();
+    let defaults = Bindings::default();
+
+    assert_eq!(bindings.turn, vec![T]);
+    assert_eq!(bindings.throttle, defaults.throttle);
+    assert_eq!(bindings.brake, defaults.brake);
+    assert_eq!(bindings.alt_volt, vec![LAlt, RAlt]);
+    assert_eq!(bindings.rewind, defaults.rewind);
+    assert_eq!(bindings.zoom_in, defaults.zoom_in);
+    assert_eq!(bindings.replay_faster, defaults.replay_faster);
+    assert_eq!(bindings.action(Space), None);
+    assert_eq!(bindings.action(R), Some(Action::Restart));
+    assert_eq!(bindings.action(Period), Some(Action::ReplayFaster));
+    assert_eq!(bindings.action(Comma), Some(Action::ReplaySlower));
+}
+
+#[test]
+fn empty_file_is_defaults() {
+    let bindings = load("empty", "").unwrap();
+    assert_eq!(bindings.action(Space), Some(Action::Turn));
+    assert_eq!(bindings.action(F3), Some(Action::Telemetry));
+}
+
+#[test]
+fn duplicate_keys_are_rejected() {
+    let error = load("duplicate", "restart = [\"Space\"]\n").err().unwrap();
+    assert!(error.contains("Space is bound to both"), "{}", error);
+
+    let error = load("held", "turn = [\"Up\"]\n").err().unwrap();
+    assert!(
+        error.contains("Up is bound to both throttle and turn"),
+        "{}",
+        error
+    );
+
+    // The same key twice for one action is fine.
+    assert!(load("repeated", "turn = [\"Space\", \"Space\"]\n").is_ok());
+}
+
+#[test]
+fn unknown_fields_and_keys_are_rejected() {
+    assert!(load("field", "jump = [\"J\"]\n").is_err());
+    assert!(load("key", "turn = [\"NoSuchKey\"]\n").is_err());
+}
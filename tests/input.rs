@@ -0,0 +1,68 @@
+use elma2::bindings::Action;
+use elma2::input::{stick_volts, FakeInput, Input, STICK_DEADZONE};
+use elma2::physics::Control;
+
+#[test]
+fn controls_of_all_sources_are_merged() {
+    let throttle = FakeInput {
+        control: Control {
+            throttle: true,
+            ..Control::default()
+        },
+        ..FakeInput::default()
+    };
+    let brake_and_volt = FakeInput {
+        control: Control {
+            brake: true,
+            volt_left: true,
+            ..Control::default()
+        },
+        rewinding: true,
+        ..FakeInput::default()
+    };
+    let input = Input::new(vec![Box::new(throttle), Box::new(brake_and_volt)]);
+
+    let control = input.control();
+    assert!(control.throttle && control.brake && control.volt_left);
+    assert!(!control.turn && !control.volt_right && !control.alt_volt);
+    assert!(input.rewinding());
+}
+
+#[test]
+fn no_sources() {
+    let mut input = Input::new(Vec::new());
+    let control = input.control();
+    assert!(!control.throttle && !control.brake && !control.turn);
+    assert!(!input.rewinding());
+    assert!(input.update().is_empty());
+}
+
+#[test]
+fn actions_are_drained() {
+    let mut input = Input::new(vec![Box::new(FakeInput {
+        actions: vec![Action::Turn, Action::VoltRight],
+        ..FakeInput::default()
+    })]);
+    input.add(Box::new(FakeInput {
+        actions: vec![Action::Restart],
+        ..FakeInput::default()
+    }));
+
+    assert_eq!(
+        input.update(),
+        vec![Action::Turn, Action::VoltRight, Action::Restart]
+    );
+    assert!(input.update().is_empty());
+}
+
+#[test]
+fn stick_deadzone() {
+    let d = STICK_DEADZONE;
+    assert_eq!(stick_volts(0.0, d), (false, false));
+    assert_eq!(stick_volts(d - 0.01, d), (false, false));
+    assert_eq!(stick_volts(-(d - 0.01), d), (false, false));
+    assert_eq!(stick_volts(d, d), (false, true));
+    assert_eq!(stick_volts(-d, d), (true, false));
+    assert_eq!(stick_volts(1.0, d), (false, true));
+    assert_eq!(stick_volts(-1.0, d), (true, false));
+}
@@ -0,0 +1,59 @@
+use cgmath::vec2;
+use elma::lev::Polygon;
+use elma::Position;
+use elma2::physics::{Control, Moto, Segments};
+
+#[allow(dead_code)]
+#[path = "../benches/legacy/mod.rs"]
+mod legacy;
+
+fn bumpy() -> Vec<Polygon> {
+    let mut vertices = vec![Position::new(0.0, 20.0)];
+    let mut x = 0.0f64;
+    while x <= 300.0 {
+        vertices.push(Position::new(x, (x * 0.7).sin() * 0.5));
+        x += 0.3;
+    }
+    vertices.push(Position::new(300.0, 20.0));
+    vec![Polygon {
+        grass: false,
+        vertices,
+    }]
+}
+
+/// Bike `(x, y, angle)` after each second, from the physics before the flat cell storage.
+const LEGACY_TRAJECTORY: [(f64, f64, f64); 4] = [
+    (5.843818281148552, 0.6141477281354119, -0.38854938405254913),
+    (3.588534907592646, 0.3284812475260206, -3.8839841839594316),
+    (0.7738766507681145, 0.12611267072212345, -3.331271018073297),
+    (0.043149267912209015, 1.1428590108068697, -4.325326930444525),
+];
+
+/// Riding on the flat cell storage must give the same trajectory as on the old grid.
+#[test]
+fn trajectory_matches_legacy_grid() {
+    let polygons = bumpy();
+    let segments = Segments::new(&polygons).unwrap();
+    let legacy = Segments::new(&polygons)
+        .unwrap()
+        .with_cells(&legacy::Legacy::new(&polygons).table);
+
+    let control = Control {
+        throttle: true,
+        ..Control::default()
+    };
+    let mut moto = Moto::new(vec2(5.0, 2.0));
+    let mut legacy_moto = moto.clone();
+    for i in 1..=400 {
+        let t = i as f64 * 0.01;
+        moto.advance(control, t, &segments, &mut ());
+        legacy_moto.advance(control, t, &legacy, &mut ());
+        assert_eq!(moto, legacy_moto, "diverged at t = {}", t);
+
+        if i % 100 == 0 {
+            let (x, y, angle) = LEGACY_TRAJECTORY[i / 100 - 1];
+            assert_eq!(moto.bike.position, vec2(x, y), "diverged at t = {}", t);
+            assert_eq!(moto.bike.angular_position, angle, "diverged at t = {}", t);
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use elma::lev::{Level, ObjectType, Top10Save};
+use elma2::check::LevelError;
+use elma2::game::GameState;
+use std::fs;
+use std::path::PathBuf;
+
+/// Load `level` saved to a file of its own.
+fn load(name: &str, mut level: Level) -> Result<(GameState, Vec<LevelError>), String> {
+    let path: PathBuf =
+        std::env::temp_dir().join(format!("elma2-{}-{}.lev", name, std::process::id()));
+    level.save(&path, Top10Save::No).unwrap();
+    let loaded = GameState::load(&path);
+    fs::remove_file(&path).unwrap();
+    loaded
+}
+
+#[test]
+fn valid_level_has_no_errors() {
+    let (_, errors) = load("valid", Level::new()).unwrap();
+    assert_eq!(errors, vec![]);
+}
+
+#[test]
+fn level_errors_are_returned() {
+    let mut level = Level::new();
+    level
+        .objects
+        .retain(|object| object.object_type != ObjectType::Exit);
+    // The level is still loaded, the caller decides what to do about its errors.
+    let (_, errors) = load("no-exit", level).unwrap();
+    assert_eq!(errors, vec![LevelError::MissingExit]);
+}
+
+#[test]
+fn missing_file_is_an_error() {
+    let path = std::env::temp_dir().join("elma2-missing.lev");
+    let error = GameState::load(&path).err().unwrap();
+    assert!(error.starts_with(&path.display().to_string()), "{}", error);
+}
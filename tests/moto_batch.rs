@@ -0,0 +1,86 @@
+use cgmath::vec2;
+use elma::lev::Polygon;
+use elma::Position;
+use elma2::physics::{Control, Moto, MotoBatch, Segments};
+
+fn ground() -> Segments {
+    let mut vertices = vec![Position::new(0.0, 20.0)];
+    let mut x = 0.0f64;
+    while x <= 100.0 {
+        vertices.push(Position::new(x, (x * 0.9).sin() * 0.3));
+        x += 0.5;
+    }
+    vertices.push(Position::new(100.0, 20.0));
+    Segments::new(&[Polygon {
+        grass: false,
+        vertices,
+    }])
+    .unwrap()
+}
+
+/// Control of bike `bike` during frame `frame`, with turns and volts now and then.
+fn control(bike: usize, frame: usize) -> Control {
+    Control {
+        throttle: !(frame + bike).is_multiple_of(7),
+        brake: (frame + bike).is_multiple_of(11),
+        turn: frame == 20 + bike,
+        volt_left: frame % 30 == bike % 5,
+        volt_right: frame % 45 == 10 + bike % 3,
+        alt_volt: bike == 0 && frame == 70,
+    }
+}
+
+#[test]
+fn batch_matches_scalar_advance() {
+    let segments = ground();
+    let positions: Vec<_> = (0..8).map(|i| vec2(5.0 + i as f64 * 10.0, 1.0)).collect();
+    let mut batch = MotoBatch::new(&positions);
+    let mut motos: Vec<Moto> = positions.iter().map(|&p| Moto::new(p)).collect();
+
+    for frame in 0..100 {
+        let t = (frame + 1) as f64 * 0.01;
+        for (i, moto) in motos.iter_mut().enumerate() {
+            batch.controls[i] = control(i, frame);
+            moto.advance(control(i, frame), t, &segments, &mut ());
+        }
+        batch.advance(t, &segments);
+
+        let batch_motos: Vec<Moto> = (0..batch.len()).map(|i| batch.moto(i)).collect();
+        assert_eq!(batch_motos, motos, "frame {}", frame);
+        assert!(batch
+            .positions()
+            .iter()
+            .zip(&motos)
+            .all(|(&position, moto)| position == moto.bike.position));
+        assert!(batch
+            .controls
+            .iter()
+            .all(|c| !c.turn && !c.volt_left && !c.volt_right && !c.alt_volt));
+    }
+
+    // Make sure the inputs did something.
+    assert!(motos.iter().all(|moto| moto.last_volt().0 > 0.0));
+    assert!(motos.iter().all(|moto| moto.last_turn() > 0.0));
+}
+
+#[test]
+fn bikes_are_stored_unchanged() {
+    let segments = ground();
+    let mut moto = Moto::new(vec2(5.0, 1.0));
+    let control = Control {
+        throttle: true,
+        volt_right: true,
+        ..Control::default()
+    };
+    moto.advance(control, 0.5, &segments, &mut ());
+
+    let mut batch = MotoBatch::new(&[vec2(20.0, 1.0)]);
+    batch.push(&moto);
+    assert_eq!(batch.len(), 2);
+    assert_eq!(batch.moto(1), moto);
+
+    let fresh = Moto::new(vec2(30.0, 1.0));
+    batch.set_moto(1, &fresh);
+    assert_eq!(batch.moto(1), fresh);
+    assert_eq!(batch.moto(0), Moto::new(vec2(20.0, 1.0)));
+}
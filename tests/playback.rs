@@ -0,0 +1,72 @@
+use elma::lev::{Level, Object, ObjectType, Polygon};
+use elma::rec::{Event, EventType, Frame, Ride};
+use elma::Position;
+use elma2::game::{GameState, FRAME_TIME};
+use elma2::replay::{simulate, Playback};
+
+fn level() -> Level {
+    let mut level = Level::new();
+    level.polygons = vec![Polygon {
+        grass: false,
+        vertices: vec![
+            Position::new(0.0, 0.0),
+            Position::new(100.0, 0.0),
+            Position::new(100.0, 20.0),
+            Position::new(0.0, 20.0),
+        ],
+    }];
+    level.objects = vec![
+        Object {
+            position: Position::new(5.0, 1.0),
+            object_type: ObjectType::Player,
+        },
+        Object {
+            position: Position::new(90.0, 1.0),
+            object_type: ObjectType::Exit,
+        },
+    ];
+    level
+}
+
+/// Throttle for the first 40 frames, then a turn.
+fn ride() -> Ride {
+    let mut ride = Ride::new();
+    for i in 0..60 {
+        let mut frame = Frame::new();
+        frame.throttle_and_dir = (i < 40) as u8;
+        ride.frames.push(frame);
+    }
+    ride.events.push(Event {
+        time: 45.0 * FRAME_TIME,
+        event_type: EventType::Turn,
+    });
+    ride
+}
+
+#[test]
+fn shows_simulated_frames() {
+    let ride = ride();
+    let mut expected = Vec::new();
+    simulate(level(), &ride, &mut (), |_, game_state, _| {
+        expected.push(game_state.moto.clone())
+    })
+    .unwrap();
+
+    let playback = Playback::new(level(), &ride).unwrap();
+    assert_eq!(playback.duration(), 59.0 * FRAME_TIME);
+
+    let mut game_state = GameState::from_level(level()).unwrap();
+    for (i, moto) in expected.iter().enumerate() {
+        let throttle = playback.show(&mut game_state, (i as f64 + 0.5) * FRAME_TIME);
+        assert_eq!(&game_state.moto, moto, "frame {}", i);
+        assert_eq!(throttle, i < 40);
+        assert!(!game_state.practice);
+    }
+
+    // Outside the ride the first and last frames are shown.
+    playback.show(&mut game_state, -1.0);
+    assert_eq!(&game_state.moto, &expected[0]);
+    playback.show(&mut game_state, 100.0);
+    assert_eq!(&game_state.moto, expected.last().unwrap());
+    assert!(game_state.moto.last_turn() > 0.0);
+}
@@ -0,0 +1,178 @@
+use elma::lev::{GravityDirection, Level, Object, ObjectType, Polygon};
+use elma::Position;
+use elma2::game::{GameState, Outcome};
+use elma2::physics::{Control, TIME_STEP};
+use elma2::rewind::{Rewind, REWIND_INTERVAL};
+
+/// Flat ground with an apple on the way to the exit, to the left of the start.
+fn game_state() -> GameState {
+    let mut level = Level::new();
+    level.polygons = vec![Polygon {
+        grass: false,
+        vertices: vec![
+            Position::new(0.0, 0.0),
+            Position::new(100.0, 0.0),
+            Position::new(100.0, 20.0),
+            Position::new(0.0, 20.0),
+        ],
+    }];
+    let object = |x, object_type| Object {
+        position: Position::new(x, 1.0),
+        object_type,
+    };
+    level.objects = vec![
+        object(50.0, ObjectType::Player),
+        object(
+            48.5,
+            ObjectType::Apple {
+                gravity: GravityDirection::None,
+                animation: 1,
+            },
+        ),
+        object(46.5, ObjectType::Exit),
+    ];
+    GameState::from_level(level).unwrap()
+}
+
+fn throttle() -> Control {
+    Control {
+        throttle: true,
+        ..Control::default()
+    }
+}
+
+/// Time of snapshot `n`.
+fn at(n: usize) -> f64 {
+    n as f64 * REWIND_INTERVAL
+}
+
+/// Whether `t` is the time of snapshot `n`. Snapshots are taken on the first step at or after
+/// each interval, so they run up to a step late each.
+fn is_snapshot(t: f64, n: usize) -> bool {
+    t >= at(n) - 1e-9 && t <= at(n) + n as f64 * TIME_STEP + 1e-9
+}
+
+#[test]
+fn rewinds_to_latest_snapshot() {
+    let mut game_state = game_state();
+    let mut rewind = Rewind::new(1000);
+    rewind.advance(&mut game_state, throttle(), at(50), &mut ());
+
+    let t = rewind.rewind(&mut game_state, at(20) + REWIND_INTERVAL * 0.5);
+    assert_eq!(t, Some(game_state.moto.time()));
+    assert!(is_snapshot(t.unwrap(), 20), "{:?}", t);
+
+    // Same as a run which never went further.
+    let mut other = self::game_state();
+    other.advance(throttle(), t.unwrap(), &mut ());
+    assert_eq!(game_state.moto, other.moto);
+    assert!(game_state.practice);
+
+    // Later snapshots are gone, the run continues from here.
+    assert_eq!(rewind.rewind(&mut game_state, at(40)), t);
+}
+
+#[test]
+fn capacity_evicts_oldest() {
+    let mut game_state = game_state();
+    let mut rewind = Rewind::new(10);
+    rewind.advance(
+        &mut game_state,
+        throttle(),
+        at(30) + REWIND_INTERVAL * 0.5,
+        &mut (),
+    );
+
+    // Snapshots 21 to 30 are kept, rewinding further stops at the oldest.
+    let t = rewind.rewind(&mut game_state, 0.0).unwrap();
+    assert!(is_snapshot(t, 21), "{}", t);
+    assert_eq!(game_state.moto.time(), t);
+    assert_eq!(rewind.rewind(&mut game_state, -1.0), Some(t));
+}
+
+#[test]
+fn rewind_past_oldest_of_a_short_run() {
+    let mut game_state = game_state();
+    let mut rewind = Rewind::new(1000);
+    rewind.advance(&mut game_state, throttle(), at(5), &mut ());
+
+    assert_eq!(rewind.rewind(&mut game_state, -10.0), Some(0.0));
+    assert_eq!(game_state.moto, self::game_state().moto);
+    assert_eq!(game_state.outcome, Outcome::Playing);
+}
+
+#[test]
+fn rewind_restores_taken_objects() {
+    let mut game_state = game_state();
+    let mut rewind = Rewind::new(100_000);
+    let mut n = 0;
+    while !game_state.taken[1] && n < 2000 {
+        n += 10;
+        rewind.advance(&mut game_state, throttle(), at(n), &mut ());
+    }
+    assert!(game_state.taken[1]);
+
+    rewind.rewind(&mut game_state, 0.0);
+    assert!(!game_state.taken[1]);
+}
+
+#[test]
+fn truncate_drops_later_snapshots() {
+    let mut game_state = game_state();
+    let mut rewind = Rewind::new(1000);
+    rewind.advance(&mut game_state, throttle(), at(40), &mut ());
+
+    rewind.truncate(at(10) + REWIND_INTERVAL * 0.5);
+    let t = rewind.rewind(&mut game_state, at(40)).unwrap();
+    assert!(is_snapshot(t, 10), "{}", t);
+
+    // Recording starts again after the kept snapshots.
+    rewind.advance(
+        &mut game_state,
+        throttle(),
+        at(15) + REWIND_INTERVAL * 0.5,
+        &mut (),
+    );
+    let t = rewind.rewind(&mut game_state, at(20)).unwrap();
+    assert!(is_snapshot(t, 15), "{}", t);
+}
+
+#[test]
+fn clear_drops_everything() {
+    let mut game_state = game_state();
+    let mut rewind = Rewind::new(1000);
+    rewind.advance(&mut game_state, throttle(), at(20), &mut ());
+    rewind.clear();
+
+    let moto = game_state.moto.clone();
+    assert_eq!(rewind.rewind(&mut game_state, 0.0), None);
+    assert_eq!(game_state.moto, moto);
+    assert!(!game_state.practice);
+}
+
+#[test]
+fn rewinding_gives_up_official_time() {
+    let finish = |rewound: bool| {
+        let mut game_state = game_state();
+        let mut rewind = Rewind::new(100_000);
+        rewind.advance(&mut game_state, throttle(), at(10), &mut ());
+        if rewound {
+            rewind.rewind(&mut game_state, at(5));
+        }
+        let mut n = 10;
+        while game_state.outcome == Outcome::Playing && n < 5000 {
+            n += 10;
+            rewind.advance(&mut game_state, throttle(), at(n), &mut ());
+        }
+        assert_eq!(game_state.outcome, Outcome::Finished);
+        game_state
+    };
+
+    let official = finish(false);
+    assert!(!official.practice);
+    assert_eq!(official.official_time(), Some(official.time()));
+
+    let rewound = finish(true);
+    assert!(rewound.practice);
+    assert_eq!(rewound.official_time(), None);
+}
@@ -0,0 +1,224 @@
+use elma::lev::{Level, Object, ObjectType, Polygon};
+use elma::Position;
+use elma2::game::{GameState, Outcome, Snapshot};
+use elma2::physics::{Control, Volt};
+use elma2::snapshot::{SnapshotError, VERSION};
+
+fn game_state() -> GameState {
+    let mut level = Level::new();
+    level.polygons = vec![Polygon {
+        grass: false,
+        vertices: vec![
+            Position::new(0.0, 0.0),
+            Position::new(100.0, 0.0),
+            Position::new(100.0, 20.0),
+            Position::new(0.0, 20.0),
+        ],
+    }];
+    level.objects = vec![
+        Object {
+            position: Position::new(5.0, 1.0),
+            object_type: ObjectType::Player,
+        },
+        Object {
+            position: Position::new(50.0, 1.0),
+            object_type: ObjectType::Exit,
+        },
+    ];
+    GameState::from_level(level).unwrap()
+}
+
+/// A level whose exit is reached by holding the throttle, the bike starts facing left.
+fn finishable() -> GameState {
+    let mut game_state = game_state();
+    game_state.level.objects[0].position = Position::new(50.0, 1.0);
+    game_state.level.objects[1].position = Position::new(46.5, 1.0);
+    game_state.restart();
+    game_state
+}
+
+/// Hold the throttle until the run ends.
+fn ride_to_end(game_state: &mut GameState) {
+    let mut t = game_state.moto.time();
+    while game_state.outcome == Outcome::Playing && t < 10.0 {
+        t += 0.1;
+        game_state.advance(throttle(), t, &mut ());
+    }
+}
+
+fn throttle() -> Control {
+    Control {
+        throttle: true,
+        ..Control::default()
+    }
+}
+
+fn round_trip(snapshot: &Snapshot) {
+    let bytes = snapshot.to_bytes();
+    assert_eq!(Snapshot::from_bytes(&bytes).as_ref(), Ok(snapshot));
+}
+
+#[test]
+fn fresh_bike() {
+    round_trip(&game_state().snapshot());
+}
+
+#[test]
+fn mid_ride() {
+    let mut game_state = game_state();
+    game_state.advance(throttle(), 0.7, &mut ());
+    round_trip(&game_state.snapshot());
+
+    // The restored run continues exactly like the original.
+    let snapshot = Snapshot::from_bytes(&game_state.snapshot().to_bytes()).unwrap();
+    let mut restored = self::game_state();
+    restored.restore(&snapshot).unwrap();
+    game_state.advance(throttle(), 1.4, &mut ());
+    restored.advance(throttle(), 1.4, &mut ());
+    assert_eq!(restored.snapshot(), game_state.snapshot());
+}
+
+#[test]
+fn after_turn_and_volt() {
+    let mut game_state = game_state();
+    game_state.advance(throttle(), 0.5, &mut ());
+    let turn = Control {
+        turn: true,
+        ..throttle()
+    };
+    game_state.advance(turn, 0.6, &mut ());
+    let volt = Control {
+        volt_right: true,
+        ..throttle()
+    };
+    game_state.advance(volt, 0.65, &mut ());
+    assert!(game_state.moto.last_turn() > 0.5);
+    assert!(game_state.moto.last_volt().0 > 0.6);
+    assert_eq!(game_state.moto.last_volt().1, Volt::Right);
+    round_trip(&game_state.snapshot());
+}
+
+#[test]
+fn after_alt_volt() {
+    let mut game_state = game_state();
+    game_state.advance(throttle(), 0.5, &mut ());
+    let alt_volt = Control {
+        alt_volt: true,
+        ..throttle()
+    };
+    game_state.advance(alt_volt, 0.55, &mut ());
+    assert!(game_state.moto.last_volt().0 > 0.5);
+    assert_eq!(game_state.moto.last_volt().1, Volt::Both);
+    round_trip(&game_state.snapshot());
+}
+
+#[test]
+fn bad_magic() {
+    let mut bytes = game_state().snapshot().to_bytes();
+    bytes[0] = b'X';
+    assert_eq!(Snapshot::from_bytes(&bytes), Err(SnapshotError::BadMagic));
+}
+
+#[test]
+fn wrong_version() {
+    let mut bytes = game_state().snapshot().to_bytes();
+    bytes[4..8].copy_from_slice(&(VERSION + 1).to_le_bytes());
+    assert_eq!(
+        Snapshot::from_bytes(&bytes),
+        Err(SnapshotError::Version(VERSION + 1))
+    );
+}
+
+#[test]
+fn truncated() {
+    let bytes = game_state().snapshot().to_bytes();
+    for length in [0, 3, 8, 100, bytes.len() - 1] {
+        assert_eq!(
+            Snapshot::from_bytes(&bytes[..length]),
+            Err(SnapshotError::Truncated),
+            "length {}",
+            length
+        );
+    }
+}
+
+#[test]
+fn bad_bool() {
+    let mut bytes = game_state().snapshot().to_bytes();
+    // Magic, version, time, three objects of six numbers, head position and velocity, then the
+    // braking flag.
+    let braking = 4 + 4 + 8 + 3 * 6 * 8 + 4 * 8;
+    assert_eq!(bytes[braking], 0);
+    bytes[braking] = 2;
+    assert_eq!(Snapshot::from_bytes(&bytes), Err(SnapshotError::BadValue));
+}
+
+#[test]
+fn bad_outcome() {
+    let mut snapshot = game_state().snapshot();
+    snapshot.outcome = Outcome::Finished;
+    let mut bytes = snapshot.to_bytes();
+    assert_eq!(*bytes.last().unwrap(), 2);
+    *bytes.last_mut().unwrap() = 3;
+    assert_eq!(Snapshot::from_bytes(&bytes), Err(SnapshotError::BadValue));
+}
+
+#[test]
+fn finishing_gives_official_time() {
+    let mut game_state = finishable();
+    ride_to_end(&mut game_state);
+    assert_eq!(game_state.outcome, Outcome::Finished);
+    assert!(!game_state.practice);
+    assert_eq!(game_state.official_time(), Some(game_state.time()));
+}
+
+#[test]
+fn respawn_at_checkpoint_is_practice() {
+    let mut game_state = finishable();
+    game_state.advance(throttle(), 0.3, &mut ());
+    game_state.set_checkpoint();
+    let checkpoint = game_state.snapshot();
+    game_state.advance(throttle(), 0.6, &mut ());
+    assert!(!game_state.practice);
+
+    assert!(game_state.respawn());
+    assert!(game_state.practice);
+    assert_eq!(game_state.snapshot(), checkpoint);
+    assert_eq!(game_state.checkpoint.as_ref(), Some(&checkpoint));
+
+    ride_to_end(&mut game_state);
+    assert_eq!(game_state.outcome, Outcome::Finished);
+    assert_eq!(game_state.official_time(), None);
+}
+
+#[test]
+fn respawn_without_checkpoint() {
+    let mut game_state = finishable();
+    game_state.advance(throttle(), 0.3, &mut ());
+    let before = game_state.snapshot();
+    assert!(!game_state.respawn());
+    assert!(!game_state.practice);
+    assert_eq!(game_state.snapshot(), before);
+}
+
+#[test]
+fn restart_clears_practice_and_keeps_checkpoint() {
+    let mut game_state = finishable();
+    game_state.advance(throttle(), 0.3, &mut ());
+    game_state.set_checkpoint();
+    let checkpoint = game_state.snapshot();
+    assert!(game_state.respawn());
+
+    game_state.restart();
+    assert!(!game_state.practice);
+    assert_eq!(game_state.moto.time(), 0.0);
+    assert_eq!(game_state.checkpoint.as_ref(), Some(&checkpoint));
+
+    ride_to_end(&mut game_state);
+    assert_eq!(game_state.official_time(), Some(game_state.time()));
+
+    // The checkpoint still works after the restart.
+    assert!(game_state.respawn());
+    assert!(game_state.practice);
+    assert_eq!(game_state.snapshot(), checkpoint);
+}